@@ -0,0 +1,122 @@
+use std::hint::black_box;
+
+use chip_8::{
+    asm::assemble,
+    chip8::Chip8,
+    consts::{PROGRAM_START_ADDRESS, SCALE},
+    filters::Filters,
+    frame::Frame,
+    palette::Palette,
+};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+/// Instructions run per benchmark iteration.
+const INSTRUCTIONS: u32 = 10_000;
+
+/// Counting in registers, mostly arithmetic with a skip and a jump.
+const TIGHT_LOOP: &str = "
+    loop:
+        ADD V0, 1
+        SE V0, 0
+        JP loop
+        ADD V1, 1
+        LD V2, V1
+        XOR V2, V0
+        JP loop
+";
+
+/// A tall sprite drawn all over the screen.
+const DRAW: &str = "
+        LD I, sprite
+    loop:
+        DRW V0, V1, 15
+        ADD V0, 3
+        ADD V1, 5
+        JP loop
+    sprite:
+        DB #FF, #81, #BD, #A5, #A5, #BD, #81, #FF
+        DB #FF, #81, #BD, #A5, #A5, #BD, #81
+";
+
+/// Scores turned into digits and registers saved and restored.
+const MEMORY: &str = "
+    loop:
+        LD I, buffer
+        ADD V0, 7
+        LD B, V0
+        LD V2, [I]
+        LD I, buffer
+        LD [I], VF
+        LD V7, [I]
+        JP loop
+    buffer:
+";
+
+fn chip8(source: &str) -> Chip8 {
+    let program = assemble(source, PROGRAM_START_ADDRESS).expect("benchmark ROM assembles");
+    let mut chip8 = Chip8::new();
+    // Draws would otherwise wait for a vblank that never comes.
+    chip8.quirks.display_wait = false;
+    chip8.load_program(&program).unwrap();
+    chip8
+}
+
+fn instructions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("instructions");
+    group.throughput(Throughput::Elements(INSTRUCTIONS as u64));
+
+    for (name, source) in [
+        ("tight_loop", TIGHT_LOOP),
+        ("draw", DRAW),
+        ("memory", MEMORY),
+    ] {
+        let mut interpreted = chip8(source);
+        group.bench_function(format!("{name}/cycle"), |b| {
+            b.iter(|| {
+                for _ in 0..INSTRUCTIONS {
+                    interpreted.cycle().unwrap();
+                }
+            })
+        });
+
+        let mut translated = chip8(source);
+        group.bench_function(format!("{name}/run"), |b| {
+            b.iter(|| translated.run(black_box(INSTRUCTIONS)).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+fn frame(c: &mut Criterion) {
+    let mut chip8 = chip8(DRAW);
+    chip8.run(1000).unwrap();
+    let palette = Palette::new([0, 0, 0], [255, 255, 255]);
+
+    let mut group = c.benchmark_group("frame");
+    for (name, filters) in [
+        ("plain", Filters::default()),
+        (
+            "all_filters",
+            Filters {
+                scanlines: true,
+                pixel_grid: true,
+                glow: true,
+                barrel: true,
+            },
+        ),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut frame = Frame::from_display(black_box(&chip8.display), &palette, SCALE);
+                filters.apply(&mut frame);
+                frame
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, instructions, frame);
+criterion_main!(benches);
@@ -0,0 +1,9 @@
+#!/bin/sh
+# Replaces the bundled database with the current upstream one. The copy in the
+# repository only has a few programs until this has been run and committed.
+set -e
+cd "$(dirname "$0")"
+url=https://raw.githubusercontent.com/chip-8/chip-8-database/master/database
+for file in programs.json sha1-hashes.json platforms.json; do
+    curl -fsSL -o "$file" "$url/$file"
+done
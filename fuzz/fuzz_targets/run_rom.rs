@@ -0,0 +1,89 @@
+//! Runs arbitrary bytes as a ROM, one instruction at a time and as translated
+//! blocks, checking that neither panics, that both end up in the same state,
+//! and that the program counter and the stack stay in bounds. The first byte
+//! toggles quirks and the timing, the rest is the program.
+
+#![no_main]
+
+use chip_8::{
+    chip8::Chip8,
+    consts::{MEMORY_SIZE, STACK_DEPTH},
+    timing::Timing,
+};
+use libfuzzer_sys::fuzz_target;
+
+const FRAMES: u32 = 50;
+const CYCLES_PER_FRAME: u32 = 200;
+
+fn chip8(flags: u8, program: &[u8]) -> Option<Chip8> {
+    let flag = |bit: u8| flags & (1 << bit) != 0;
+
+    let mut chip8 = Chip8::new();
+    let quirks = &mut chip8.quirks;
+    quirks.vf_reset ^= flag(0);
+    quirks.shift_use_vy ^= flag(1);
+    quirks.bnnn ^= flag(2);
+    quirks.store_load_increments_i ^= flag(3);
+    quirks.memory_increment_by_x ^= flag(4);
+    quirks.display_wait ^= flag(5);
+    quirks.wrap ^= flag(6);
+    if flag(7) {
+        chip8.timing = Timing::Vip;
+    }
+
+    // Both runs need the same CXNN results.
+    chip8.seed(0);
+    chip8.load_program(program).ok()?;
+    Some(chip8)
+}
+
+fn assert_same_state(interpreted: &Chip8, translated: &Chip8) {
+    assert_eq!(interpreted.program_counter(), translated.program_counter());
+    assert_eq!(interpreted.index_register(), translated.index_register());
+    assert_eq!(interpreted.registers(), translated.registers());
+    assert_eq!(interpreted.stack(), translated.stack());
+    assert_eq!(interpreted.delay_timer, translated.delay_timer);
+    assert_eq!(interpreted.sound_timer, translated.sound_timer);
+    assert_eq!(interpreted.display, translated.display);
+    assert_eq!(interpreted.memory()[..], translated.memory()[..]);
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&flags, program)) = data.split_first() else {
+        return;
+    };
+    let (Some(mut interpreted), Some(mut translated)) =
+        (chip8(flags, program), chip8(flags, program))
+    else {
+        return;
+    };
+
+    for frame in 0..FRAMES {
+        let mut result = Ok(());
+        for _ in 0..CYCLES_PER_FRAME {
+            result = interpreted.cycle();
+            if result.is_err() {
+                break;
+            }
+            assert!((interpreted.program_counter() as usize) < MEMORY_SIZE);
+            assert!(interpreted.stack().len() <= STACK_DEPTH);
+        }
+
+        assert_eq!(translated.run(CYCLES_PER_FRAME), result);
+        assert_same_state(&interpreted, &translated);
+        if result.is_err() {
+            return;
+        }
+
+        // Press and release keys, so key waits get somewhere.
+        let key = (frame / 2 % 16) as u8;
+        for chip8 in [&mut interpreted, &mut translated] {
+            if frame % 2 == 0 {
+                chip8.press_key(key);
+            } else {
+                chip8.release_key(key);
+            }
+            chip8.vblank();
+        }
+    }
+});
@@ -0,0 +1,17 @@
+#!/bin/sh
+# Seeds the run_rom corpus with the programs in seeds/, and the test ROMs when
+# there are any, each behind a zero flags byte so they start out with the
+# default quirks.
+set -e
+cd "$(dirname "$0")"
+mkdir -p corpus/run_rom
+for source in seeds/*.asm; do
+    name=$(basename "$source" .asm)
+    cargo run --quiet --manifest-path ../Cargo.toml -- asm "$source" -o "corpus/run_rom/$name.ch8"
+    { printf '\000'; cat "corpus/run_rom/$name.ch8"; } > "corpus/run_rom/$name"
+    rm "corpus/run_rom/$name.ch8"
+done
+for rom in ../tests/*.ch8; do
+    [ -e "$rom" ] || continue
+    { printf '\000'; cat "$rom"; } > "corpus/run_rom/$(basename "$rom")"
+done
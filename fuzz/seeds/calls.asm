@@ -0,0 +1,17 @@
+; Nested subroutines, timers, random numbers and the keypad.
+loop:
+    CALL outer
+    RND V3, #0F
+    SKNP V3
+    LD V4, K
+    JP loop
+outer:
+    LD DT, V3
+    CALL inner
+    RET
+inner:
+    LD V5, DT
+    LD ST, V5
+    JP V0, done
+done:
+    RET
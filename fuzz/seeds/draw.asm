@@ -0,0 +1,14 @@
+; A tall sprite and the font drawn all over the screen.
+    CLS
+loop:
+    LD I, sprite
+    DRW V0, V1, 15
+    LD F, V2
+    DRW V1, V0, 5
+    ADD V0, 3
+    ADD V1, 5
+    ADD V2, 1
+    JP loop
+sprite:
+    DB #FF, #81, #BD, #A5, #A5, #BD, #81, #FF
+    DB #FF, #81, #BD, #A5, #A5, #BD, #81
@@ -0,0 +1,11 @@
+; Counting in registers, mostly arithmetic with a skip and a jump.
+loop:
+    ADD V0, 1
+    SE V0, 0
+    JP loop
+    ADD V1, 1
+    LD V2, V1
+    XOR V2, V0
+    SHR V2
+    SUBN V3, V2
+    JP loop
@@ -0,0 +1,11 @@
+; Scores turned into digits and registers saved and restored.
+loop:
+    LD I, buffer
+    ADD V0, 7
+    LD B, V0
+    LD V2, [I]
+    LD I, buffer
+    LD [I], VF
+    LD V7, [I]
+    JP loop
+buffer:
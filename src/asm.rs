@@ -0,0 +1,288 @@
+use std::{collections::HashMap, fmt};
+
+/// Error found while assembling, with the 1-based line it happened on.
+#[derive(Debug, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Operand {
+    Register(u16),
+    I,
+    IndirectI,
+    DelayTimer,
+    SoundTimer,
+    Key,
+    Font,
+    BigFont,
+    Bcd,
+    Number(u16),
+    Label(String),
+}
+
+struct Statement {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<Operand>,
+}
+
+/// Assembles Cowgod-style mnemonics (the same ones the disassembler prints)
+/// into a program that starts at `start_address`.
+///
+/// Lines may start with a `label:`, comments start with `;`, numbers can be
+/// decimal, `0x`/`#`/`$` hex or `0b` binary, and `DB`/`DW` emit raw bytes and
+/// words.
+pub fn assemble(source: &str, start_address: usize) -> Result<Vec<u8>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = start_address;
+
+    // First pass: parse everything and find out where the labels are.
+    for (i, line) in source.lines().enumerate() {
+        let error = |message: String| AsmError {
+            line: i + 1,
+            message,
+        };
+
+        let mut line = line.split(';').next().unwrap_or("").trim();
+
+        if let Some((label, rest)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_identifier(label) {
+                return Err(error(format!("invalid label '{label}'")));
+            }
+            if labels.insert(label.to_string(), address as u16).is_some() {
+                return Err(error(format!("label '{label}' is defined twice")));
+            }
+            line = rest.trim();
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        let operands = operands
+            .split(',')
+            .map(str::trim)
+            .filter(|operand| !operand.is_empty())
+            .map(parse_operand)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(error)?;
+
+        address += match mnemonic.as_str() {
+            "DB" => operands.len(),
+            "DW" => operands.len() * 2,
+            _ => 2,
+        };
+
+        statements.push(Statement {
+            line: i + 1,
+            mnemonic,
+            operands,
+        });
+    }
+
+    // Second pass: encode with every label known.
+    let mut program = Vec::new();
+    for statement in statements {
+        let error = |message: String| AsmError {
+            line: statement.line,
+            message,
+        };
+
+        let operands = statement
+            .operands
+            .into_iter()
+            .map(|operand| match operand {
+                Operand::Label(label) => labels
+                    .get(&label)
+                    .map(|&address| Operand::Number(address))
+                    .ok_or_else(|| format!("unknown label '{label}'")),
+                operand => Ok(operand),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(error)?;
+
+        match statement.mnemonic.as_str() {
+            "DB" => {
+                for operand in &operands {
+                    program.push(number(operand, 0xFF).map_err(error)? as u8);
+                }
+            }
+            "DW" => {
+                for operand in &operands {
+                    program.extend(number(operand, 0xFFFF).map_err(error)?.to_be_bytes());
+                }
+            }
+            mnemonic => {
+                let instruction = encode(mnemonic, &operands).map_err(error)?;
+                program.extend(instruction.to_be_bytes());
+            }
+        }
+    }
+
+    Ok(program)
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_operand(operand: &str) -> Result<Operand, String> {
+    let upper = operand.to_ascii_uppercase();
+
+    let parsed = match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::DelayTimer,
+        "ST" => Operand::SoundTimer,
+        "K" => Operand::Key,
+        "F" => Operand::Font,
+        "HF" => Operand::BigFont,
+        "B" => Operand::Bcd,
+        _ if upper.len() == 2 && upper.starts_with('V') => Operand::Register(
+            u16::from_str_radix(&upper[1..], 16)
+                .map_err(|_| format!("invalid register '{operand}'"))?,
+        ),
+        _ if upper.starts_with(|c: char| c.is_ascii_digit() || c == '#' || c == '$') => {
+            Operand::Number(
+                parse_number(&upper).ok_or_else(|| format!("invalid number '{operand}'"))?,
+            )
+        }
+        _ if is_identifier(operand) => Operand::Label(operand.to_string()),
+        _ => return Err(format!("invalid operand '{operand}'")),
+    };
+
+    Ok(parsed)
+}
+
+fn parse_number(number: &str) -> Option<u16> {
+    if let Some(hex) = number
+        .strip_prefix("0X")
+        .or_else(|| number.strip_prefix('#'))
+        .or_else(|| number.strip_prefix('$'))
+    {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = number.strip_prefix("0B") {
+        u16::from_str_radix(binary, 2).ok()
+    } else {
+        number.parse().ok()
+    }
+}
+
+fn number(operand: &Operand, max: u16) -> Result<u16, String> {
+    match operand {
+        Operand::Number(value) if *value <= max => Ok(*value),
+        Operand::Number(value) => Err(format!("{value:#X} does not fit in {max:#X}")),
+        operand => Err(format!("expected a number, found {operand:?}")),
+    }
+}
+
+fn encode(mnemonic: &str, operands: &[Operand]) -> Result<u16, String> {
+    use Operand::*;
+
+    let addr = |operand| number(operand, 0xFFF);
+    let byte = |operand| number(operand, 0xFF);
+
+    let instruction = match (mnemonic, operands) {
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SYS", [nnn]) => addr(nnn)?,
+        ("JP", [nnn]) => 0x1000 | addr(nnn)?,
+        ("JP", [Register(0), nnn]) => 0xB000 | addr(nnn)?,
+        ("CALL", [nnn]) => 0x2000 | addr(nnn)?,
+        ("SE", [Register(x), Register(y)]) => 0x5000 | x << 8 | y << 4,
+        ("SE", [Register(x), nn]) => 0x3000 | x << 8 | byte(nn)?,
+        ("SNE", [Register(x), Register(y)]) => 0x9000 | x << 8 | y << 4,
+        ("SNE", [Register(x), nn]) => 0x4000 | x << 8 | byte(nn)?,
+        ("LD", [Register(x), Register(y)]) => 0x8000 | x << 8 | y << 4,
+        ("LD", [Register(x), DelayTimer]) => 0xF007 | x << 8,
+        ("LD", [Register(x), Key]) => 0xF00A | x << 8,
+        ("LD", [Register(x), IndirectI]) => 0xF065 | x << 8,
+        ("LD", [Register(x), nn]) => 0x6000 | x << 8 | byte(nn)?,
+        ("LD", [I, nnn]) => 0xA000 | addr(nnn)?,
+        ("LD", [DelayTimer, Register(x)]) => 0xF015 | x << 8,
+        ("LD", [SoundTimer, Register(x)]) => 0xF018 | x << 8,
+        ("LD", [Font, Register(x)]) => 0xF029 | x << 8,
+        ("LD", [BigFont, Register(x)]) => 0xF030 | x << 8,
+        ("LD", [Bcd, Register(x)]) => 0xF033 | x << 8,
+        ("LD", [IndirectI, Register(x)]) => 0xF055 | x << 8,
+        ("ADD", [I, Register(x)]) => 0xF01E | x << 8,
+        ("ADD", [Register(x), Register(y)]) => 0x8004 | x << 8 | y << 4,
+        ("ADD", [Register(x), nn]) => 0x7000 | x << 8 | byte(nn)?,
+        ("OR", [Register(x), Register(y)]) => 0x8001 | x << 8 | y << 4,
+        ("AND", [Register(x), Register(y)]) => 0x8002 | x << 8 | y << 4,
+        ("XOR", [Register(x), Register(y)]) => 0x8003 | x << 8 | y << 4,
+        ("SUB", [Register(x), Register(y)]) => 0x8005 | x << 8 | y << 4,
+        ("SHR", [Register(x)]) => 0x8006 | x << 8 | x << 4,
+        ("SHR", [Register(x), Register(y)]) => 0x8006 | x << 8 | y << 4,
+        ("SUBN", [Register(x), Register(y)]) => 0x8007 | x << 8 | y << 4,
+        ("SHL", [Register(x)]) => 0x800E | x << 8 | x << 4,
+        ("SHL", [Register(x), Register(y)]) => 0x800E | x << 8 | y << 4,
+        ("RND", [Register(x), nn]) => 0xC000 | x << 8 | byte(nn)?,
+        ("DRW", [Register(x), Register(y), n]) => 0xD000 | x << 8 | y << 4 | number(n, 0xF)?,
+        ("SKP", [Register(x)]) => 0xE09E | x << 8,
+        ("SKNP", [Register(x)]) => 0xE0A1 | x << 8,
+        _ => {
+            return Err(format!(
+                "invalid instruction '{mnemonic}' with operands {operands:?}"
+            ))
+        }
+    };
+
+    Ok(instruction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::disassemble;
+
+    #[test]
+    fn test_labels_and_data() {
+        let source = "
+            start:  LD I, sprite  ; point at the sprite
+                    DRW V0, V1, 2
+                    JP start
+            sprite: DB 0b11110000, #90
+        ";
+
+        assert_eq!(
+            assemble(source, 0x200),
+            Ok(vec![0xA2, 0x06, 0xD0, 0x12, 0x12, 0x00, 0xF0, 0x90])
+        );
+    }
+
+    #[test]
+    fn test_errors_point_at_line() {
+        let error = assemble("CLS\nJP nowhere", 0x200).unwrap_err();
+        assert_eq!(error.line, 2);
+
+        let error = assemble("LD V0, 0x100", 0x200).unwrap_err();
+        assert_eq!(error.line, 1);
+    }
+
+    #[test]
+    fn test_disassembly_round_trips() {
+        for instruction in 0..=u16::MAX {
+            let source = disassemble(instruction);
+
+            assert_eq!(
+                assemble(&source, 0x200),
+                Ok(instruction.to_be_bytes().to_vec()),
+                "{source}"
+            );
+        }
+    }
+}
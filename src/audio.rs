@@ -0,0 +1,221 @@
+use std::{f32::consts::PI, fmt, str::FromStr};
+
+use sdl2::audio::AudioCallback;
+
+use crate::consts::{BEEP_FREQUENCY, BEEP_VOLUME, BEEP_WAVEFORM};
+
+pub const SAMPLE_RATE: i32 = 44100;
+
+/// Time it takes the beeper to fade in or out, so starting and stopping a beep
+/// does not click.
+const ENVELOPE_SECONDS: f32 = 0.005;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+    Noise,
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "square" => Ok(Waveform::Square),
+            "sine" => Ok(Waveform::Sine),
+            "triangle" => Ok(Waveform::Triangle),
+            "noise" => Ok(Waveform::Noise),
+            _ => Err(format!("unknown waveform '{s}'")),
+        }
+    }
+}
+
+impl fmt::Display for Waveform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Waveform::Square => "square",
+            Waveform::Sine => "sine",
+            Waveform::Triangle => "triangle",
+            Waveform::Noise => "noise",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AudioSettings {
+    pub frequency: f32,
+    pub volume: f32,
+    pub waveform: Waveform,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            frequency: BEEP_FREQUENCY,
+            volume: BEEP_VOLUME,
+            waveform: BEEP_WAVEFORM,
+        }
+    }
+}
+
+/// Tone generator for the sound timer. The beeper is gated on and off rather
+/// than paused, so the envelope can ramp the volume smoothly.
+pub struct Beeper {
+    settings: AudioSettings,
+    sample_rate: f32,
+    phase_inc: f32,
+    phase: f32,
+    gain: f32,
+    gain_step: f32,
+    gate: bool,
+    noise_state: u32,
+    noise_value: f32,
+}
+
+impl Beeper {
+    pub fn new(settings: AudioSettings, sample_rate: i32) -> Self {
+        Beeper {
+            settings,
+            sample_rate: sample_rate as f32,
+            phase_inc: settings.frequency / sample_rate as f32,
+            phase: 0.0,
+            gain: 0.0,
+            gain_step: 1.0 / (ENVELOPE_SECONDS * sample_rate as f32),
+            gate: false,
+            noise_state: 0xACE1,
+            noise_value: 1.0,
+        }
+    }
+
+    pub fn set_gate(&mut self, on: bool) {
+        self.gate = on;
+    }
+
+    /// Changes the pitch from the configured one, as the CHIP-8X sound board
+    /// can.
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.phase_inc = frequency / self.sample_rate;
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        for x in out.iter_mut() {
+            let target = if self.gate { 1.0 } else { 0.0 };
+            if self.gain < target {
+                self.gain = (self.gain + self.gain_step).min(target);
+            } else if self.gain > target {
+                self.gain = (self.gain - self.gain_step).max(target);
+            }
+
+            if self.gain == 0.0 {
+                // Restart every beep from the same point of the waveform.
+                self.phase = 0.0;
+                *x = 0.0;
+                continue;
+            }
+
+            *x = self.sample() * self.settings.volume * self.gain;
+            self.advance();
+        }
+    }
+
+    fn sample(&self) -> f32 {
+        match self.settings.waveform {
+            Waveform::Square => {
+                if self.phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sine => (2.0 * PI * self.phase).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            Waveform::Noise => self.noise_value,
+        }
+    }
+
+    fn advance(&mut self) {
+        let previous = self.phase;
+        self.phase = (self.phase + self.phase_inc) % 1.0;
+
+        // Noise picks a new level every half period, so it still follows the
+        // configured frequency.
+        let crossed_half = previous < 0.5 && self.phase >= 0.5;
+        if self.phase < previous || crossed_half {
+            // 16-bit Galois LFSR
+            let bit = self.noise_state & 1;
+            self.noise_state >>= 1;
+            if bit == 1 {
+                self.noise_state ^= 0xB400;
+            }
+            self.noise_value = if bit == 1 { 1.0 } else { -1.0 };
+        }
+    }
+}
+
+impl AudioCallback for Beeper {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [Self::Channel]) {
+        self.fill(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(waveform: Waveform, gate: bool, samples: usize) -> Vec<f32> {
+        let settings = AudioSettings {
+            waveform,
+            ..Default::default()
+        };
+        let mut beeper = Beeper::new(settings, SAMPLE_RATE);
+        beeper.set_gate(gate);
+
+        let mut out = vec![0.0; samples];
+        beeper.fill(&mut out);
+        out
+    }
+
+    #[test]
+    fn test_silent_when_gate_is_closed() {
+        assert!(render(Waveform::Square, false, 1000)
+            .iter()
+            .all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_envelope_ramps_without_clicks() {
+        let samples = render(Waveform::Square, true, 1000);
+        let max_step = BEEP_VOLUME / (ENVELOPE_SECONDS * SAMPLE_RATE as f32);
+
+        assert!(samples[0].abs() <= max_step + f32::EPSILON);
+        assert_eq!(samples[999].abs(), BEEP_VOLUME);
+    }
+
+    #[test]
+    fn test_waveforms_stay_within_volume() {
+        for waveform in [
+            Waveform::Square,
+            Waveform::Sine,
+            Waveform::Triangle,
+            Waveform::Noise,
+        ] {
+            let samples = render(waveform, true, SAMPLE_RATE as usize / 10);
+
+            assert!(samples
+                .iter()
+                .all(|s| s.abs() <= BEEP_VOLUME + f32::EPSILON));
+            assert!(samples.iter().any(|&s| s > 0.0) && samples.iter().any(|&s| s < 0.0));
+        }
+    }
+
+    #[test]
+    fn test_parse_waveform() {
+        assert_eq!("Triangle".parse(), Ok(Waveform::Triangle));
+        assert!("sawtooth".parse::<Waveform>().is_err());
+    }
+}
@@ -0,0 +1,334 @@
+/// What the 1802 reads and writes through.
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+}
+
+/// The COSMAC VIP's RCA CDP1802, enough of it to run the machine code
+/// routines programs call with `0NNN`. There are no interrupts or DMA, the
+/// `EF` lines are never asserted and `INP` reads 0.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Cdp1802 {
+    /// R0-RF.
+    pub r: [u16; 16],
+    /// Which register is the program counter.
+    pub p: u8,
+    /// Which register points at memory for the `X` instructions.
+    pub x: u8,
+    pub d: u8,
+    pub df: bool,
+    pub t: u8,
+    pub ie: bool,
+    pub q: bool,
+}
+
+impl Cdp1802 {
+    fn fetch(&mut self, bus: &mut impl Bus) -> u8 {
+        let p = self.p as usize;
+        let byte = bus.read(self.r[p]);
+        self.r[p] = self.r[p].wrapping_add(1);
+        byte
+    }
+
+    fn rx(&self) -> u16 {
+        self.r[self.x as usize]
+    }
+
+    /// `D + value + carry`, setting DF to the carry out.
+    fn add(&mut self, value: u8, carry: bool) {
+        let sum = self.d as u16 + value as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    /// `minuend - subtrahend - borrow`, setting DF when nothing was borrowed.
+    fn subtract(&mut self, minuend: u8, subtrahend: u8, borrow: bool) {
+        let difference = minuend as i16 - subtrahend as i16 - borrow as i16;
+        self.d = difference as u8;
+        self.df = difference >= 0;
+    }
+
+    /// Runs one instruction and returns how many machine cycles it took.
+    pub fn step(&mut self, bus: &mut impl Bus) -> u32 {
+        let opcode = self.fetch(bus);
+        let n = (opcode & 0xF) as usize;
+
+        match opcode >> 4 {
+            // IDL waits for an interrupt or DMA, neither of which comes.
+            0x0 if n == 0 => {}
+            0x0 => self.d = bus.read(self.r[n]),
+            0x1 => self.r[n] = self.r[n].wrapping_add(1),
+            0x2 => self.r[n] = self.r[n].wrapping_sub(1),
+            0x3 => {
+                let condition = match n & 7 {
+                    0 => true,
+                    1 => self.q,
+                    2 => self.d == 0,
+                    3 => self.df,
+                    _ => false,
+                };
+                // 38 is SKP, which skips the branch address either way.
+                let taken = n != 8 && condition != (n >= 8);
+                let p = self.p as usize;
+                if taken {
+                    let low = bus.read(self.r[p]);
+                    self.r[p] = self.r[p] & 0xFF00 | low as u16;
+                } else {
+                    self.r[p] = self.r[p].wrapping_add(1);
+                }
+            }
+            0x4 => {
+                self.d = bus.read(self.r[n]);
+                self.r[n] = self.r[n].wrapping_add(1);
+            }
+            0x5 => bus.write(self.r[n], self.d),
+            0x6 => {
+                let x = self.x as usize;
+                match n {
+                    0 => self.r[x] = self.r[x].wrapping_add(1),
+                    // OUT
+                    1..=7 => self.r[x] = self.r[x].wrapping_add(1),
+                    // INP
+                    9.. => {
+                        self.d = 0;
+                        bus.write(self.r[x], 0);
+                    }
+                    _ => {}
+                }
+            }
+            0x7 => self.step_7(bus, n),
+            0x8 => self.d = self.r[n] as u8,
+            0x9 => self.d = (self.r[n] >> 8) as u8,
+            0xA => self.r[n] = self.r[n] & 0xFF00 | self.d as u16,
+            0xB => self.r[n] = self.r[n] & 0x00FF | (self.d as u16) << 8,
+            0xC => {
+                self.long_branch(bus, n);
+                return 3;
+            }
+            0xD => self.p = n as u8,
+            0xE => self.x = n as u8,
+            _ => self.step_f(bus, n),
+        }
+
+        2
+    }
+
+    fn step_7(&mut self, bus: &mut impl Bus, n: usize) {
+        let x = self.x as usize;
+        match n {
+            // RET and DIS
+            0x0 | 0x1 => {
+                let byte = bus.read(self.rx());
+                self.r[x] = self.r[x].wrapping_add(1);
+                self.x = byte >> 4;
+                self.p = byte & 0xF;
+                self.ie = n == 0;
+            }
+            // LDXA
+            0x2 => {
+                self.d = bus.read(self.rx());
+                self.r[x] = self.r[x].wrapping_add(1);
+            }
+            // STXD
+            0x3 => {
+                bus.write(self.rx(), self.d);
+                self.r[x] = self.r[x].wrapping_sub(1);
+            }
+            // ADC
+            0x4 => {
+                let value = bus.read(self.rx());
+                self.add(value, self.df);
+            }
+            // SDB
+            0x5 => {
+                let value = bus.read(self.rx());
+                self.subtract(value, self.d, !self.df);
+            }
+            // SHRC
+            0x6 => {
+                let carry = self.d & 1 == 1;
+                self.d = self.d >> 1 | (self.df as u8) << 7;
+                self.df = carry;
+            }
+            // SMB
+            0x7 => {
+                let value = bus.read(self.rx());
+                self.subtract(self.d, value, !self.df);
+            }
+            // SAV
+            0x8 => bus.write(self.rx(), self.t),
+            // MARK
+            0x9 => {
+                self.t = self.x << 4 | self.p;
+                bus.write(self.r[2], self.t);
+                self.x = self.p;
+                self.r[2] = self.r[2].wrapping_sub(1);
+            }
+            0xA => self.q = false,
+            0xB => self.q = true,
+            // ADCI
+            0xC => {
+                let value = self.fetch(bus);
+                self.add(value, self.df);
+            }
+            // SDBI
+            0xD => {
+                let value = self.fetch(bus);
+                self.subtract(value, self.d, !self.df);
+            }
+            // SHLC
+            0xE => {
+                let carry = self.d & 0x80 != 0;
+                self.d = self.d << 1 | self.df as u8;
+                self.df = carry;
+            }
+            // SMBI
+            _ => {
+                let value = self.fetch(bus);
+                self.subtract(self.d, value, !self.df);
+            }
+        }
+    }
+
+    /// `CN`: long branches, which take the next two bytes as an address, and
+    /// long skips, which skip them.
+    fn long_branch(&mut self, bus: &mut impl Bus, n: usize) {
+        let condition = match n & 3 {
+            0 if n == 0xC => self.ie,
+            0 => true,
+            1 => self.q,
+            2 => self.d == 0,
+            _ => self.df,
+        };
+        let p = self.p as usize;
+
+        match n {
+            // NOP
+            0x4 => {}
+            // LBR, LBQ, LBZ, LBDF and LSKP, LBNQ, LBNZ, LBNF
+            0x0..=0x3 | 0x8..=0xB => {
+                let taken = n != 8 && condition != (n >= 8);
+                if taken {
+                    let high = bus.read(self.r[p]);
+                    let low = bus.read(self.r[p].wrapping_add(1));
+                    self.r[p] = u16::from_be_bytes([high, low]);
+                } else {
+                    self.r[p] = self.r[p].wrapping_add(2);
+                }
+            }
+            // LSNQ, LSNZ, LSNF and LSIE, LSQ, LSZ, LSDF
+            _ => {
+                if condition != (n < 8) {
+                    self.r[p] = self.r[p].wrapping_add(2);
+                }
+            }
+        }
+    }
+
+    fn step_f(&mut self, bus: &mut impl Bus, n: usize) {
+        // FN reads M(R(X)), F8-FF the byte after the instruction.
+        let value = if n < 8 {
+            bus.read(self.rx())
+        } else if n == 0xE {
+            0
+        } else {
+            self.fetch(bus)
+        };
+
+        match n & 7 {
+            0 => self.d = value,
+            1 => self.d |= value,
+            2 => self.d &= value,
+            3 => self.d ^= value,
+            4 => self.add(value, false),
+            5 => self.subtract(value, self.d, false),
+            6 if n == 6 => {
+                self.df = self.d & 1 == 1;
+                self.d >>= 1;
+            }
+            6 => {
+                self.df = self.d & 0x80 != 0;
+                self.d <<= 1;
+            }
+            _ => self.subtract(self.d, value, false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Memory([u8; 0x100]);
+
+    impl Bus for Memory {
+        fn read(&mut self, address: u16) -> u8 {
+            self.0[address as usize % 0x100]
+        }
+
+        fn write(&mut self, address: u16, value: u8) {
+            self.0[address as usize % 0x100] = value;
+        }
+    }
+
+    /// Runs `program` from 0 with R3 as the program counter until it sets P
+    /// to 4.
+    fn run(program: &[u8]) -> (Cdp1802, Memory) {
+        let mut memory = Memory([0; 0x100]);
+        memory.0[..program.len()].copy_from_slice(program);
+        let mut cpu = Cdp1802 {
+            p: 3,
+            x: 2,
+            ..Default::default()
+        };
+        cpu.r[2] = 0xFF;
+
+        let mut cycles = 0;
+        while cpu.p != 4 {
+            cycles += cpu.step(&mut memory);
+            assert!(cycles < 1000, "ran away");
+        }
+        (cpu, memory)
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        // R2.0 = 0x20; M(R2) = 0xF0; D += 0x20; D = M(R2) - D; D += 1 + DF
+        let (cpu, memory) = run(&[
+            0xF8, 0xF0, 0xF8, 0x20, 0xA2, 0xF8, 0xF0, 0x52, 0xFC, 0x20, 0xF5, 0x7C, 0x01, 0xD4,
+        ]);
+
+        assert_eq!(memory.0[0x20], 0xF0);
+        // 0xF0 + 0x20 = 0x10 carrying; 0xF0 - 0x10 = 0xE0 not borrowing;
+        // 0xE0 + 1 + 1
+        assert_eq!(cpu.d, 0xE2);
+        assert!(!cpu.df);
+    }
+
+    #[test]
+    fn test_loop_and_stack() {
+        // R7.0 = 3; D = 0; loop: push D; D += 1; R7 -= 1; D = R7.0; branch
+        // back while it isn't 0
+        let (cpu, memory) = run(&[
+            0xF8, 0x03, 0xA7, 0xF8, 0x00, 0x73, 0xFC, 0x01, 0x27, 0x87, 0x3A, 0x05, 0xD4,
+        ]);
+
+        assert_eq!(cpu.d, 0);
+        assert_eq!(memory.0[0xFD..], [1, 2, 0]);
+        assert_eq!(cpu.r[2], 0xFC);
+        assert_eq!(cpu.r[3], 0x0D);
+    }
+
+    #[test]
+    fn test_long_branch_and_shifts() {
+        // D = 0x81; SHL; LBDF 0x0010; ... 0x0010: SHRC; D4
+        let mut program = vec![0xF8, 0x81, 0xFE, 0xC3, 0x00, 0x10];
+        program.resize(0x10, 0);
+        program.extend([0x76, 0xD4]);
+        let (cpu, _) = run(&program);
+
+        // 0x81 << 1 = 0x02 carrying, then back with the carry in bit 7
+        assert_eq!(cpu.d, 0x81);
+        assert!(!cpu.df);
+    }
+}
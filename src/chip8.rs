@@ -1,21 +1,42 @@
-use std::time::{Duration, Instant};
+use std::{
+    fmt,
+    io::{self, Read},
+    time::Instant,
+};
 
-use rand::Rng;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::{
+    cdp1802::{Bus, Cdp1802},
+    chip8x::ColorBoard,
     consts::{
-        BNNN, DISPLAY_WAIT, FONT_START_ADRESS, FRAME_TIME_60HZ, HEIGHT, PROGRAM_START_ADDRESS,
-        SHIFT_USE_VY, STORE_LOAD_INCREMENTS_I, VF_RESET, WIDTH,
+        HEIGHT, HIRES_HEADER, HIRES_START_ADDRESS, MEMORY_SIZE, PROGRAM_START_ADDRESS, STACK_DEPTH,
+        VIP_DISPLAY_ADDRESS, VIP_MEMORY_STACK_DEPTH, VIP_REGISTERS_ADDRESS, VIP_STACK_ADDRESS,
+        WIDTH,
     },
-    font::FONT_CHARACTERS,
+    font::{Font, BIG_DIGIT_SIZE, SMALL_FONT_SIZE},
+    instruction::Instruction,
+    layout::Layout,
+    quirks::Quirks,
+    timing::{vip_cycles, Timing, VIP_CYCLES_PER_FRAME},
+    translator::{Block, MAX_BLOCK_LENGTH, MAX_TRANSLATED},
+    variant::Variant,
 };
 
 #[allow(unused)]
 pub struct Chip8 {
-    pub display: [[bool; WIDTH as usize]; HEIGHT as usize],
+    /// [`HEIGHT`] rows, or as many as the variant has.
+    pub display: Vec<[bool; WIDTH]>,
     program_counter: u16,
     index_register: u16,
-    memory: [u8; 0x1000],
+    memory: [u8; MEMORY_SIZE],
+    /// Instructions decoded so far, by address.
+    decoded: Box<[Option<Instruction>; MEMORY_SIZE]>,
+    /// Where in `translated` the blocks translated so far are, by start
+    /// address.
+    blocks: Box<[Option<(u32, u32)>; MEMORY_SIZE]>,
+    /// The instructions of every block, one after the other.
+    translated: Vec<Instruction>,
     stack: Vec<u16>,
     pub delay_timer: u8,
     pub sound_timer: u8,
@@ -23,22 +44,166 @@ pub struct Chip8 {
     last_update: Instant,
     global_timer: f32,
     pub keypad: [bool; 16],
-    pub waiting_for_key: bool,
-    pub last_key_pressed: Option<u8>,
-    last_draw_time: Instant,
+    key_wait: Option<KeyWait>,
+    vblank_pending: bool,
+    waiting_for_vblank: bool,
+    machine_cycles: u64,
+    pub quirks: Quirks,
+    pub timing: Timing,
+    /// Most subroutines nested at once before `2NNN` faults. A stack in
+    /// memory never holds more than [`VIP_MEMORY_STACK_DEPTH`].
+    pub stack_depth: usize,
+    /// Keeps return addresses in memory where the VIP did, so programs that
+    /// read or change them there see them. Always on with [`Layout::Vip`].
+    pub memory_stack: bool,
+    layout: Layout,
+    font: Font,
+    variant: Variant,
+    /// Only there with [`Variant::Chip8x`].
+    colors: Option<ColorBoard>,
+    /// The CHIP-8X second keypad, read by `EXF2` and `EXF5`.
+    pub second_keypad: [bool; 16],
+    /// The last value `FXF8` wrote to the CHIP-8X I/O port.
+    pub output_port: Option<u8>,
+    /// A value waiting on the CHIP-8X I/O port for `FXFB` to read. With
+    /// nothing waiting the port reads 0.
+    pub input_port: Option<u8>,
+    rng: StdRng,
+}
+
+/// Progress of an `FX0A`, which like on the COSMAC VIP only finishes once a
+/// key has been pressed and released again. Timers keep running meanwhile.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum KeyWait {
+    Press,
+    Release(u8),
+    Released(u8),
+}
+
+/// Why a program could not be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+    Empty,
+    /// `size` is at most `max + 1` for programs read from a stream.
+    TooLarge {
+        size: usize,
+        max: usize,
+    },
+    Io(io::Error),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Empty => write!(f, "the program is empty"),
+            LoadError::TooLarge { size, max } => write!(
+                f,
+                "the program is {size} bytes, but only {max} bytes fit in memory"
+            ),
+            LoadError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+/// Why an instruction could not run. The program counter is left on it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fault {
+    StackUnderflow,
+    StackOverflow,
+    UnknownInstruction(u16),
+    /// A `0NNN` whose machine code did not return within a second.
+    RunawayMachineRoutine(u16),
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::StackUnderflow => write!(f, "return with an empty stack"),
+            Fault::StackOverflow => write!(f, "call with a full stack"),
+            Fault::UnknownInstruction(opcode) => write!(f, "unknown instruction {opcode:04X}"),
+            Fault::RunawayMachineRoutine(address) => {
+                write!(f, "machine code at {address:03X} did not return")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Fault {}
+
+/// Machine cycles a `0NNN` routine may run for, a second on the VIP.
+const MACHINE_ROUTINE_CYCLES: u64 = 60 * VIP_CYCLES_PER_FRAME;
+/// The VIP's monitor ROM, which the 1802 can read but not write.
+const VIP_MONITOR_ADDRESS: u16 = 0x8000;
+
+/// Addresses past the end of memory wrap around to the start.
+fn wrap(address: u16) -> u16 {
+    address % MEMORY_SIZE as u16
+}
+
+/// Where the `index`th return address is kept with a memory stack.
+fn stack_entry(index: usize) -> usize {
+    (VIP_STACK_ADDRESS + MEMORY_SIZE - 2 * (index + 1)) % MEMORY_SIZE
+}
+
+/// Checks that a program of `size` bytes fits in memory when loaded at
+/// `address`.
+pub fn check_program_size(size: usize, address: usize) -> Result<(), LoadError> {
+    check_program_fits(size, address, MEMORY_SIZE)
+}
+
+/// Checks that a program of `size` bytes loaded at `address` ends before
+/// `end`.
+fn check_program_fits(size: usize, address: usize, end: usize) -> Result<(), LoadError> {
+    let max = end.saturating_sub(address);
+
+    if size == 0 {
+        Err(LoadError::Empty)
+    } else if size > max {
+        Err(LoadError::TooLarge { size, max })
+    } else {
+        Ok(())
+    }
+}
+
+/// Reads a whole program from `reader` and checks that it fits in memory when
+/// loaded at `address`. Stops one byte past what fits, so endless readers are
+/// rejected too.
+pub fn read_program(reader: impl Read, address: usize) -> Result<Vec<u8>, LoadError> {
+    let max = MEMORY_SIZE.saturating_sub(address);
+    let mut program = Vec::new();
+    reader.take(max as u64 + 1).read_to_end(&mut program)?;
+    check_program_size(program.len(), address)?;
+
+    Ok(program)
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Chip8::new()
+    }
 }
 
 impl Chip8 {
     pub fn new() -> Self {
-        let mut memory = [0; 0x1000];
+        let mut memory = [0; MEMORY_SIZE];
 
-        Chip8::store_font(&mut memory);
+        let font = Font::default();
+        Chip8::store_font(&mut memory, &font, Layout::default().font_address());
 
         Chip8 {
-            display: [[false; WIDTH as usize]; HEIGHT as usize],
+            display: vec![[false; WIDTH]; HEIGHT],
             program_counter: PROGRAM_START_ADDRESS as u16,
             index_register: 0,
             memory,
+            decoded: Box::new([None; MEMORY_SIZE]),
+            blocks: Box::new([None; MEMORY_SIZE]),
+            translated: Vec::new(),
             stack: Vec::new(),
             delay_timer: 0,
             sound_timer: 0,
@@ -46,59 +211,241 @@ impl Chip8 {
             last_update: Instant::now(),
             global_timer: 0.0,
             keypad: [false; 16],
-            waiting_for_key: false,
-            last_key_pressed: None,
-            last_draw_time: Instant::now(),
+            key_wait: None,
+            vblank_pending: false,
+            waiting_for_vblank: false,
+            machine_cycles: 0,
+            quirks: Quirks::default(),
+            timing: Timing::default(),
+            stack_depth: STACK_DEPTH,
+            memory_stack: false,
+            layout: Layout::default(),
+            font,
+            variant: Variant::default(),
+            colors: None,
+            second_keypad: [false; 16],
+            output_port: None,
+            input_port: None,
+            rng: StdRng::from_os_rng(),
         }
     }
 
+    /// Makes `CXNN` produce the same numbers on every run.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    pub fn registers(&self) -> &[u8; 0x0010] {
+        &self.variable_registers
+    }
+
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    pub fn memory(&self) -> &[u8; MEMORY_SIZE] {
+        &self.memory
+    }
+
+    /// COSMAC VIP machine cycles taken by the instructions run so far, only
+    /// counted with [`Timing::Vip`].
+    pub fn machine_cycles(&self) -> u64 {
+        self.machine_cycles
+    }
+
+    /// Whether the last instruction was a draw held back until the next
+    /// vertical blank.
+    pub fn waiting_for_vblank(&self) -> bool {
+        self.waiting_for_vblank
+    }
+
     pub fn release_key(&mut self, key: u8) {
         self.keypad[key as usize] = false;
+
+        if self.key_wait == Some(KeyWait::Release(key)) {
+            self.key_wait = Some(KeyWait::Released(key));
+        }
     }
 
     pub fn press_key(&mut self, key: u8) {
         self.keypad[key as usize] = true;
+
+        if self.key_wait == Some(KeyWait::Press) {
+            self.key_wait = Some(KeyWait::Release(key));
+        }
     }
 
-    pub fn load_program(&mut self, program: &Vec<u8>) {
-        for (i, byte) in program.iter().enumerate() {
-            self.memory[PROGRAM_START_ADDRESS + i] = *byte;
+    /// Lets go of every key on both keypads. A key wait that had seen a press
+    /// goes back to waiting for a new one, so the release never arrives.
+    pub fn release_all_keys(&mut self) {
+        self.keypad = [false; 16];
+        self.second_keypad = [false; 16];
+
+        if let Some(KeyWait::Release(_)) = self.key_wait {
+            self.key_wait = Some(KeyWait::Press);
         }
     }
 
-    fn store_font(memory: &mut [u8; 0x1000]) {
-        let mut i = 0;
-        for chr in FONT_CHARACTERS.iter() {
-            for byte in chr.bitmap {
-                memory[FONT_START_ADRESS + i] = byte;
-                i += 1;
-            }
+    /// Loads a program at the usual `0x200`.
+    pub fn load_program(&mut self, program: &[u8]) -> Result<(), LoadError> {
+        self.load_program_at(program, PROGRAM_START_ADDRESS)
+    }
+
+    /// Loads a program at `address` and starts running it from there, e.g.
+    /// `0x600` for ETI-660 programs.
+    pub fn load_program_at(&mut self, program: &[u8], address: usize) -> Result<(), LoadError> {
+        check_program_fits(program.len(), address, self.layout.program_end())?;
+
+        self.memory[address..address + program.len()].copy_from_slice(program);
+        self.decoded.fill(None);
+        self.blocks.fill(None);
+        self.translated.clear();
+        self.program_counter = address as u16;
+
+        let hires = address == PROGRAM_START_ADDRESS && program.starts_with(&HIRES_HEADER);
+        if hires && self.variant == Variant::Chip8 {
+            self.set_variant(Variant::Hires);
+        }
+        if hires && self.variant == Variant::Hires {
+            self.program_counter = HIRES_START_ADDRESS as u16;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a whole program from `reader` (a file, an archive entry, stdin...)
+    /// and loads it at `address`.
+    #[allow(unused)]
+    pub fn load_program_from(
+        &mut self,
+        reader: impl Read,
+        address: usize,
+    ) -> Result<(), LoadError> {
+        let program = read_program(reader, address)?;
+        self.load_program_at(&program, address)
+    }
+
+    /// Stores the small digits at `address` and the big ones right after.
+    fn store_font(memory: &mut [u8; MEMORY_SIZE], font: &Font, address: usize) {
+        memory[address..address + SMALL_FONT_SIZE].copy_from_slice(&font.small);
+        let big = address + SMALL_FONT_SIZE;
+        memory[big..big + font.big.len()].copy_from_slice(&font.big);
+    }
+
+    fn clear_font(&mut self) {
+        let address = self.layout.font_address();
+        self.memory[address..address + self.font.size()].fill(0);
+        self.decoded.fill(None);
+        self.blocks.fill(None);
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    /// Replaces the font. Meant to be called before loading a program.
+    pub fn set_font(&mut self, font: Font) {
+        self.clear_font();
+        Chip8::store_font(&mut self.memory, &font, self.layout.font_address());
+        self.font = font;
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Moves the font, and with [`Layout::Vip`] maps the stack, registers and
+    /// display into memory. Meant to be called before loading a program.
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.clear_font();
+        Chip8::store_font(&mut self.memory, &self.font, layout.font_address());
+
+        self.layout = layout;
+        if layout == Layout::Vip {
+            self.sync_registers();
+            self.sync_display();
+        }
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Switches instruction sets, resizing the display to the variant's and
+    /// with [`Variant::Chip8x`] adding the colour board. Meant to be called
+    /// before loading a program.
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+        self.colors = (variant == Variant::Chip8x).then(ColorBoard::default);
+        self.display = vec![[false; WIDTH]; variant.display_height()];
+        self.decoded.fill(None);
+        self.blocks.fill(None);
+        if self.layout == Layout::Vip {
+            self.sync_display();
+        }
+    }
+
+    pub fn colors(&self) -> Option<&ColorBoard> {
+        self.colors.as_ref()
+    }
+
+    fn memory_stack(&self) -> bool {
+        self.memory_stack || self.layout == Layout::Vip
+    }
+
+    fn max_stack_depth(&self) -> usize {
+        if self.memory_stack() {
+            self.stack_depth.min(VIP_MEMORY_STACK_DEPTH)
+        } else {
+            self.stack_depth
         }
     }
 
-    pub fn draw_sprite(&mut self, sprite: Vec<u8>, mut x: u8, mut y: u8) {
+    /// Draws the `height` rows of the sprite at `address` in memory.
+    fn draw_sprite(&mut self, address: usize, height: usize, mut x: u8, mut y: u8) {
+        let rows = self.display.len();
         x %= WIDTH as u8;
-        y %= HEIGHT as u8;
+        y %= rows as u8;
 
-        for (i, &row) in sprite.iter().enumerate() {
-            for j in 0..8 as usize {
+        for i in 0..height {
+            let row = self.memory[(address + i) % MEMORY_SIZE];
+            for j in 0..8 {
                 let pixel = ((row >> (7 - j)) & 1) == 1;
 
-                if y as usize + i >= HEIGHT || x as usize + j >= WIDTH {
+                let (mut row, mut column) = (y as usize + i, x as usize + j);
+                if self.quirks.wrap {
+                    row %= rows;
+                    column %= WIDTH;
+                } else if row >= rows || column >= WIDTH {
                     continue;
                 }
 
-                if pixel && self.display[y as usize + i][x as usize + j] {
+                if pixel && self.display[row][column] {
                     self.variable_registers[0xF] = 1;
                 }
 
-                self.display[y as usize + i][x as usize + j] ^= pixel;
+                self.display[row][column] ^= pixel;
             }
         }
     }
 
     fn clear_display(&mut self) {
-        self.display = [[false; WIDTH]; HEIGHT]
+        self.display.fill([false; WIDTH]);
+    }
+
+    /// Signals the 60Hz vertical blank interrupt: ticks the timers and lets a
+    /// draw that is waiting for the display go through.
+    pub fn vblank(&mut self) {
+        self.decrease_timers();
+        self.vblank_pending = true;
     }
 
     pub fn decrease_timers(&mut self) {
@@ -111,223 +458,985 @@ impl Chip8 {
         }
     }
 
-    pub fn cycle(&mut self) {
-        // Fetch
-        let instruction = ((self.memory[self.program_counter as usize] as u16) << 8)
-            | self.memory[(self.program_counter + 1) as usize] as u16;
-
-        self.program_counter += 2;
-
-        // X: The second nibble. Used to look up one of the 16 registers (VX) from V0 through VF.
-        // Y: The third nibble. Also used to look up one of the 16 registers (VY) from V0 through VF.
-        // N: The fourth nibble. A 4-bit number.
-        // NN: The second byte (third and fourth nibbles). An 8-bit immediate number.
-        // NNN: The second, third and fourth nibbles. A 12-bit immediate memory address.
-        let x = ((instruction >> 8) & 0xF) as usize;
-        let y = ((instruction >> 4) & 0xF) as usize;
-        let n = instruction & 0xF;
-        let nn = instruction & 0xFF;
-        let nnn = instruction & 0xFFF;
-
-        let vx = self.variable_registers[x];
-        let vy = self.variable_registers[y];
-
-        // Decode / Execute
-        match (instruction >> 12) & 0xF {
-            0x0 => match instruction {
-                0x00E0 => self.clear_display(),
-                0x00EE => self.program_counter = self.stack.pop().expect("0x00EE: stack is empty"),
-                _ => println!(
-                    "{:#06X}: execute machine language routine instruction detected!",
-                    instruction
-                ),
-            },
-            0x1 => self.program_counter = nnn,
-            0x2 => {
-                self.stack.push(self.program_counter);
-                self.program_counter = nnn;
+    fn increment_index_after_store_load(&mut self, x: usize) {
+        if self.quirks.store_load_increments_i {
+            if self.quirks.memory_increment_by_x {
+                self.index_register = self.index_register.wrapping_add(x as u16);
+            } else {
+                self.index_register = self.index_register.wrapping_add(x as u16 + 1);
             }
-            0x3 => {
-                if vx as u16 == nn {
-                    self.program_counter += 2;
-                }
+        }
+    }
+
+    /// Runs one instruction, counting the machine cycles it would have taken
+    /// on the COSMAC VIP when timing it like one.
+    pub fn cycle(&mut self) -> Result<(), Fault> {
+        let address = self.program_counter;
+        let instruction = self.decoded(address);
+
+        if self.timing != Timing::Vip {
+            return self.execute(instruction);
+        }
+
+        let opcode = self.fetch(address);
+        let vx = self.variable_registers[((opcode >> 8) & 0xF) as usize];
+        let skipped = self.skips(instruction);
+        self.execute(instruction)?;
+
+        // A draw held back for the display idles until the next frame, which
+        // the frame clock accounts for; it costs its cycles once it runs.
+        if !self.waiting_for_vblank {
+            self.machine_cycles += vip_cycles(opcode, vx, skipped) as u64;
+        }
+        Ok(())
+    }
+
+    fn fetch(&self, address: u16) -> u16 {
+        u16::from_be_bytes([
+            self.memory[address as usize],
+            self.memory[wrap(address + 1) as usize],
+        ])
+    }
+
+    /// The instruction at `address`, decoded the first time it runs and
+    /// cached until memory under it is written.
+    fn decoded(&mut self, address: u16) -> Instruction {
+        match self.decoded[address as usize] {
+            Some(instruction) => instruction,
+            None => {
+                let instruction = self.variant.decode(self.fetch(address));
+                self.decoded[address as usize] = Some(instruction);
+                instruction
             }
-            0x4 => {
-                if vx as u16 != nn {
-                    self.program_counter += 2;
-                }
+        }
+    }
+
+    /// Writes to memory as a program does, which with [`Layout::Vip`] can
+    /// change the registers or the display.
+    fn write(&mut self, address: usize, value: u8) {
+        let address = address % MEMORY_SIZE;
+        self.store(address, value);
+
+        if self.layout != Layout::Vip {
+            return;
+        }
+        match address {
+            VIP_REGISTERS_ADDRESS..VIP_DISPLAY_ADDRESS => {
+                self.variable_registers[address - VIP_REGISTERS_ADDRESS] = value;
             }
-            0x5 => {
-                if vx == vy {
-                    self.program_counter += 2;
+            VIP_DISPLAY_ADDRESS.. => {
+                let offset = address - VIP_DISPLAY_ADDRESS;
+                let (row, column) = (offset / (WIDTH / 8), offset % (WIDTH / 8) * 8);
+                for bit in 0..8 {
+                    self.display[row][column + bit] = (value >> (7 - bit)) & 1 == 1;
                 }
             }
-            0x6 => self.variable_registers[x] = nn as u8,
-            0x7 => {
-                self.variable_registers[x] = vx.wrapping_add(nn as u8);
+            _ => {}
+        }
+    }
+
+    /// Runs the 1802 machine code at `nnn` until it returns to the
+    /// interpreter with `SEP R4`, its registers set up as the VIP interpreter
+    /// kept them: R2 the stack, R3 the program counter, R5 the CHIP-8 one,
+    /// R6 and R7 pointing at VX and VY, R8 the timers, RA the index register
+    /// and RB.1 the display page.
+    fn run_machine_routine(&mut self, nnn: u16) -> Result<(), Fault> {
+        let mut cpu = Cdp1802 {
+            p: 3,
+            x: 2,
+            ..Default::default()
+        };
+        cpu.r[2] = (VIP_STACK_ADDRESS - 1 - 2 * self.stack.len()) as u16;
+        cpu.r[3] = nnn;
+        cpu.r[5] = self.program_counter;
+        cpu.r[6] = VIP_REGISTERS_ADDRESS as u16 | (nnn >> 8 & 0xF);
+        cpu.r[7] = VIP_REGISTERS_ADDRESS as u16 | (nnn >> 4 & 0xF);
+        cpu.r[8] = u16::from_be_bytes([self.delay_timer, self.sound_timer]);
+        cpu.r[0xA] = self.index_register;
+        cpu.r[0xB] = VIP_DISPLAY_ADDRESS as u16 & 0xFF00;
+
+        let mut cycles = 0;
+        while cpu.p != 4 {
+            if cycles > MACHINE_ROUTINE_CYCLES {
+                return Err(Fault::RunawayMachineRoutine(nnn));
+            }
+            cycles += cpu.step(&mut VipBus(self)) as u64;
+        }
+
+        [self.delay_timer, self.sound_timer] = cpu.r[8].to_be_bytes();
+        self.index_register = wrap(cpu.r[0xA]);
+        self.program_counter = wrap(cpu.r[5]);
+        if self.timing == Timing::Vip {
+            self.machine_cycles += cycles;
+        }
+        Ok(())
+    }
+
+    /// Copies V0-VF into memory after an instruction that may have changed
+    /// them.
+    fn sync_registers(&mut self) {
+        for i in 0..self.variable_registers.len() {
+            let address = VIP_REGISTERS_ADDRESS + i;
+            if self.memory[address] != self.variable_registers[i] {
+                self.store(address, self.variable_registers[i]);
+            }
+        }
+    }
+
+    /// Copies the display into memory, a byte for every 8 pixels. Only the
+    /// top 32 rows of a hi-res display fit.
+    fn sync_display(&mut self) {
+        for offset in 0..HEIGHT * WIDTH / 8 {
+            let (row, column) = (offset / (WIDTH / 8), offset % (WIDTH / 8) * 8);
+            let value = self.display[row][column..column + 8]
+                .iter()
+                .fold(0, |value, &pixel| (value << 1) | pixel as u8);
+            let address = VIP_DISPLAY_ADDRESS + offset;
+            if self.memory[address] != value {
+                self.store(address, value);
+            }
+        }
+    }
+
+    fn store(&mut self, address: usize, value: u8) {
+        self.memory[address] = value;
+        self.invalidate(address);
+    }
+
+    fn invalidate(&mut self, address: usize) {
+        // Both instructions that include this byte have to be decoded again,
+        // and so do the blocks they are in.
+        self.decoded[address] = None;
+        self.decoded[(address + MEMORY_SIZE - 1) % MEMORY_SIZE] = None;
+
+        let first = address.saturating_sub(2 * MAX_BLOCK_LENGTH - 1);
+        for start in first..=address {
+            if self.blocks[start].is_some_and(|(_, len)| start + 2 * len as usize > address) {
+                self.blocks[start] = None;
             }
-            0x8 => match instruction & 0xF {
-                0x0 => self.variable_registers[x] = vy,
-                0x1 => {
-                    self.variable_registers[x] |= vy;
-                    if VF_RESET {
-                        self.variable_registers[0xF] = 0;
+        }
+    }
+
+    /// Runs `cycles` instructions, with the same results as calling
+    /// [`Chip8::cycle`] that many times but a translated block at a time.
+    /// VIP timing needs the cost of every instruction, so it still runs them
+    /// one by one.
+    pub fn run(&mut self, cycles: u32) -> Result<(), Fault> {
+        let mut remaining = cycles as usize;
+
+        while remaining > 0 {
+            let block = match self.timing {
+                Timing::Fixed => self.block(self.program_counter),
+                Timing::Vip => None,
+            };
+
+            match block {
+                Some((offset, len)) if len as usize <= remaining => {
+                    for i in offset..offset + len {
+                        self.execute(self.translated[i as usize])?;
                     }
+                    remaining -= len as usize;
                 }
-                0x2 => {
-                    self.variable_registers[x] &= vy;
-                    if VF_RESET {
-                        self.variable_registers[0xF] = 0;
-                    }
+                _ => {
+                    self.cycle()?;
+                    remaining -= 1;
                 }
-                0x3 => {
-                    self.variable_registers[x] ^= vy;
-                    if VF_RESET {
-                        self.variable_registers[0xF] = 0;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Where the block at `address` is in `translated`, translating it first
+    /// if needed.
+    fn block(&mut self, address: u16) -> Option<(u32, u32)> {
+        if let Some(block) = self.blocks[address as usize] {
+            return Some(block);
+        }
+
+        let block = Block::translate(&self.memory, self.variant, address);
+        if block.is_empty() {
+            return None;
+        }
+        // With the VIP layout any instruction can write V0-VF to memory, so
+        // code running over them has to run an instruction at a time.
+        if self.layout == Layout::Vip && address as usize + 2 * block.len() > VIP_REGISTERS_ADDRESS
+        {
+            return None;
+        }
+
+        // Blocks that were written over stay behind, so start over once in a
+        // while.
+        if self.translated.len() + block.len() > MAX_TRANSLATED {
+            self.blocks.fill(None);
+            self.translated.clear();
+        }
+
+        let entry = (self.translated.len() as u32, block.len() as u32);
+        self.translated.extend(block.instructions);
+        self.blocks[address as usize] = Some(entry);
+        Some(entry)
+    }
+
+    fn execute(&mut self, instruction: Instruction) -> Result<(), Fault> {
+        let result = self.execute_instruction(instruction);
+
+        if self.layout == Layout::Vip {
+            self.sync_registers();
+            if matches!(instruction, Instruction::Clear | Instruction::Draw(..)) {
+                self.sync_display();
+            }
+        }
+
+        result
+    }
+
+    /// Whether `instruction` is a skip whose condition holds, so that it
+    /// skips the next instruction.
+    fn skips(&self, instruction: Instruction) -> bool {
+        let v = |register: u8| self.variable_registers[register as usize];
+        // Like the VIP, only look at the low nibble of a key.
+        let key = |register: u8| (v(register) & 0xF) as usize;
+
+        match instruction {
+            Instruction::SkipIfEqual(x, nn) => v(x) == nn,
+            Instruction::SkipIfNotEqual(x, nn) => v(x) != nn,
+            Instruction::SkipIfRegistersEqual(x, y) => v(x) == v(y),
+            Instruction::SkipIfRegistersNotEqual(x, y) => v(x) != v(y),
+            Instruction::SkipIfKey(x) => self.keypad[key(x)],
+            Instruction::SkipIfNotKey(x) => !self.keypad[key(x)],
+            Instruction::SkipIfSecondKey(x) => self.second_keypad[key(x)],
+            Instruction::SkipIfNotSecondKey(x) => !self.second_keypad[key(x)],
+            _ => false,
+        }
+    }
+
+    fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), Fault> {
+        let address = self.program_counter;
+        self.program_counter = wrap(address + 2);
+        self.waiting_for_vblank = false;
+
+        let register = |register: u8| register as usize;
+
+        match instruction {
+            Instruction::Clear => self.clear_display(),
+            Instruction::Return => match self.stack.pop() {
+                Some(_) if self.memory_stack() => {
+                    let entry = stack_entry(self.stack.len());
+                    let return_address = [self.memory[entry], self.memory[entry + 1]];
+                    self.program_counter = wrap(u16::from_be_bytes(return_address));
+                }
+                Some(return_address) => self.program_counter = return_address,
+                None => {
+                    self.program_counter = address;
+                    return Err(Fault::StackUnderflow);
+                }
+            },
+            // Without the VIP's memory there is nothing for machine code to
+            // work on, so calls to it are skipped.
+            Instruction::MachineRoutine(nnn) => {
+                if self.layout == Layout::Vip {
+                    if let Err(fault) = self.run_machine_routine(nnn) {
+                        self.program_counter = address;
+                        return Err(fault);
                     }
                 }
-                0x4 => {
-                    let (result, overflow) = vx.overflowing_add(vy);
-                    self.variable_registers[x] = result;
-                    self.variable_registers[0xF] = overflow as u8;
+            }
+            Instruction::Jump(nnn) => self.program_counter = nnn,
+            Instruction::Call(nnn) => {
+                if self.stack.len() >= self.max_stack_depth() {
+                    self.program_counter = address;
+                    return Err(Fault::StackOverflow);
                 }
-                0x5 => {
-                    let (result, underflow) = vx.overflowing_sub(vy);
-                    self.variable_registers[x] = result;
-                    self.variable_registers[0xF] = (!underflow) as u8;
+                if self.memory_stack() {
+                    let entry = stack_entry(self.stack.len());
+                    let [high, low] = self.program_counter.to_be_bytes();
+                    self.write(entry, high);
+                    self.write(entry + 1, low);
                 }
-                0x6 => {
-                    let mut register = vx;
-                    if SHIFT_USE_VY {
-                        register = vy;
-                    }
-                    let shifted_bit = register & 0x1;
-                    self.variable_registers[x] = register >> 1;
-                    self.variable_registers[0xF] = shifted_bit;
+                self.stack.push(self.program_counter);
+                self.program_counter = nnn;
+            }
+            Instruction::SkipIfEqual(..)
+            | Instruction::SkipIfNotEqual(..)
+            | Instruction::SkipIfRegistersEqual(..)
+            | Instruction::SkipIfRegistersNotEqual(..)
+            | Instruction::SkipIfKey(_)
+            | Instruction::SkipIfNotKey(_)
+            | Instruction::SkipIfSecondKey(_)
+            | Instruction::SkipIfNotSecondKey(_) => {
+                if self.skips(instruction) {
+                    self.program_counter = wrap(self.program_counter + 2);
                 }
-                0x7 => {
-                    let (result, underflow) = vy.overflowing_sub(vx);
-                    self.variable_registers[x] = result;
-                    self.variable_registers[0xF] = (!underflow) as u8;
+            }
+            Instruction::Load(x, nn) => self.variable_registers[register(x)] = nn,
+            Instruction::Add(x, nn) => {
+                let vx = &mut self.variable_registers[register(x)];
+                *vx = vx.wrapping_add(nn);
+            }
+            Instruction::Move(x, y) => {
+                self.variable_registers[register(x)] = self.variable_registers[register(y)]
+            }
+            Instruction::Or(x, y) => {
+                self.variable_registers[register(x)] |= self.variable_registers[register(y)];
+                if self.quirks.vf_reset {
+                    self.variable_registers[0xF] = 0;
                 }
-                0xE => {
-                    let mut register = vx;
-                    if SHIFT_USE_VY {
-                        register = vy;
-                    }
-                    let shifted_bit = (register >> 7) & 0x1;
-                    self.variable_registers[x] = register << 1;
-                    self.variable_registers[0xF] = shifted_bit;
+            }
+            Instruction::And(x, y) => {
+                self.variable_registers[register(x)] &= self.variable_registers[register(y)];
+                if self.quirks.vf_reset {
+                    self.variable_registers[0xF] = 0;
                 }
-                _ => panic!("{:#06X}: unknown instruction!", instruction),
-            },
-            0x9 => {
-                if vx != vy {
-                    self.program_counter += 2;
+            }
+            Instruction::Xor(x, y) => {
+                self.variable_registers[register(x)] ^= self.variable_registers[register(y)];
+                if self.quirks.vf_reset {
+                    self.variable_registers[0xF] = 0;
                 }
             }
-            0xA => self.index_register = nnn,
-            0xB => match BNNN {
-                true => {
-                    let v0 = self.variable_registers[0];
-                    self.program_counter = nnn + v0 as u16;
+            Instruction::AddRegisters(x, y) => {
+                let (vx, vy) = (
+                    self.variable_registers[register(x)],
+                    self.variable_registers[register(y)],
+                );
+                let (result, overflow) = vx.overflowing_add(vy);
+                self.variable_registers[register(x)] = result;
+                self.variable_registers[0xF] = overflow as u8;
+            }
+            Instruction::Subtract(x, y) => {
+                let (vx, vy) = (
+                    self.variable_registers[register(x)],
+                    self.variable_registers[register(y)],
+                );
+                let (result, underflow) = vx.overflowing_sub(vy);
+                self.variable_registers[register(x)] = result;
+                self.variable_registers[0xF] = (!underflow) as u8;
+            }
+            Instruction::ShiftRight(x, y) => {
+                let mut value = self.variable_registers[register(x)];
+                if self.quirks.shift_use_vy {
+                    value = self.variable_registers[register(y)];
                 }
-                false => {
-                    let xnn = ((x as u16) << 8) | nn;
-                    self.program_counter = xnn + vx as u16;
+                let shifted_bit = value & 0x1;
+                self.variable_registers[register(x)] = value >> 1;
+                self.variable_registers[0xF] = shifted_bit;
+            }
+            Instruction::SubtractReversed(x, y) => {
+                let (vx, vy) = (
+                    self.variable_registers[register(x)],
+                    self.variable_registers[register(y)],
+                );
+                let (result, underflow) = vy.overflowing_sub(vx);
+                self.variable_registers[register(x)] = result;
+                self.variable_registers[0xF] = (!underflow) as u8;
+            }
+            Instruction::ShiftLeft(x, y) => {
+                let mut value = self.variable_registers[register(x)];
+                if self.quirks.shift_use_vy {
+                    value = self.variable_registers[register(y)];
                 }
-            },
-            0xC => {
-                let random: u8 = rand::rng().random();
-                self.variable_registers[x] = random & nn as u8;
-            }
-            0xD => {
-                if DISPLAY_WAIT {
-                    let time_since_last_draw = self.last_draw_time.elapsed();
-                    let min_draw_interval = Duration::from_secs_f32(FRAME_TIME_60HZ);
-
-                    if time_since_last_draw < min_draw_interval {
-                        self.program_counter -= 2;
-                        return;
+                let shifted_bit = (value >> 7) & 0x1;
+                self.variable_registers[register(x)] = value << 1;
+                self.variable_registers[0xF] = shifted_bit;
+            }
+            Instruction::LoadIndex(nnn) => self.index_register = nnn,
+            Instruction::JumpWithOffset(nnn) => {
+                // BXNN jumps to XNN plus VX rather than V0.
+                let offset = match self.quirks.bnnn {
+                    true => self.variable_registers[0],
+                    false => self.variable_registers[(nnn >> 8) as usize],
+                };
+                self.program_counter = wrap(nnn + offset as u16);
+            }
+            Instruction::Random(x, nn) => {
+                let random: u8 = self.rng.random();
+                self.variable_registers[register(x)] = random & nn;
+            }
+            Instruction::Draw(x, y, n) => {
+                if self.quirks.display_wait {
+                    if !self.vblank_pending {
+                        self.program_counter = address;
+                        self.waiting_for_vblank = true;
+                        return Ok(());
                     }
 
-                    self.last_draw_time = Instant::now();
+                    self.vblank_pending = false;
                 }
 
                 self.variable_registers[0xF] = 0;
 
-                let sprite = self.memory
-                    [self.index_register as usize..(self.index_register + n) as usize]
-                    .to_vec();
-
-                self.draw_sprite(sprite, vx, vy);
+                let (vx, vy) = (
+                    self.variable_registers[register(x)],
+                    self.variable_registers[register(y)],
+                );
+                self.draw_sprite(self.index_register as usize, n as usize, vx, vy);
             }
-            0xE => match instruction & 0xFF {
-                0x9E => {
-                    if self.keypad[vx as usize] {
-                        self.program_counter += 2;
-                    }
+            Instruction::LoadDelayTimer(x) => {
+                self.variable_registers[register(x)] = self.delay_timer
+            }
+            Instruction::SetDelayTimer(x) => {
+                self.delay_timer = self.variable_registers[register(x)]
+            }
+            Instruction::SetSoundTimer(x) => {
+                self.sound_timer = self.variable_registers[register(x)]
+            }
+            Instruction::AddToIndex(x) => {
+                self.index_register = self
+                    .index_register
+                    .wrapping_add(self.variable_registers[register(x)] as u16);
+            }
+            Instruction::WaitForKey(x) => match self.key_wait {
+                Some(KeyWait::Released(key)) => {
+                    self.variable_registers[register(x)] = key;
+                    self.key_wait = None;
                 }
-                0xA1 => {
-                    if !self.keypad[vx as usize] {
-                        self.program_counter += 2;
-                    }
+                Some(_) => self.program_counter = address,
+                None => {
+                    // A key that is already down counts once it is
+                    // released, as the VIP only scans the keypad.
+                    self.key_wait = Some(match self.keypad.iter().position(|&down| down) {
+                        Some(key) => KeyWait::Release(key as u8),
+                        None => KeyWait::Press,
+                    });
+                    self.program_counter = address;
                 }
-                _ => panic!("{:#06X}: unknown instruction!", instruction),
             },
-            0xF => match instruction & 0xFF {
-                0x07 => self.variable_registers[x] = self.delay_timer,
-                0x15 => self.delay_timer = self.variable_registers[x],
-                0x18 => self.sound_timer = self.variable_registers[x],
-                0x1E => {
-                    self.index_register += vx as u16;
+            Instruction::LoadFontCharacter(x) => {
+                self.index_register = self.layout.font_address() as u16
+                    + self.variable_registers[register(x)] as u16 * 5
+            }
+            Instruction::LoadBigFontCharacter(x) => {
+                let digit = self.variable_registers[register(x)] as usize;
+                // Before SUPER-CHIP there were no big digits to point at.
+                if self.font.big.is_empty() {
+                    self.program_counter = address;
+                    return Err(Fault::UnknownInstruction(0xF030 | (x as u16) << 8));
                 }
-                0x0A => {
-                    self.waiting_for_key = true;
-
-                    if let Some(key) = self.last_key_pressed {
-                        self.variable_registers[x] = key;
-                        self.waiting_for_key = false;
-                        self.last_key_pressed = None;
-                    } else {
-                        self.program_counter -= 2;
-                    }
+                self.index_register =
+                    (self.layout.font_address() + SMALL_FONT_SIZE + digit * BIG_DIGIT_SIZE) as u16;
+            }
+            Instruction::StoreBcd(x) => {
+                let vx = self.variable_registers[register(x)];
+                let digits = [vx / 100, (vx / 10) % 10, vx % 10];
+
+                for (i, digit) in digits.into_iter().enumerate() {
+                    self.write(self.index_register as usize + i, digit);
+                }
+            }
+            Instruction::StoreRegisters(x) => {
+                for i in 0..=register(x) {
+                    self.write(self.index_register as usize + i, self.variable_registers[i]);
                 }
-                0x29 => self.index_register = FONT_START_ADRESS as u16 + vx as u16 * 5,
-                0x33 => {
-                    let digits = [vx / 100, (vx / 10) % 10, vx % 10];
 
-                    for i in 0..3 {
-                        self.memory[self.index_register as usize + i] = digits[i];
-                    }
+                self.increment_index_after_store_load(register(x));
+            }
+            Instruction::LoadRegisters(x) => {
+                let start = self.index_register as usize;
+                for i in 0..=register(x) {
+                    self.variable_registers[i] = self.memory[(start + i) % MEMORY_SIZE];
                 }
-                0x55 => {
-                    self.variable_registers[0..=x]
-                        .iter()
-                        .enumerate()
-                        .for_each(|(i, r)| {
-                            self.memory[self.index_register as usize + i] = *r;
-                        });
-
-                    if STORE_LOAD_INCREMENTS_I {
-                        self.index_register += (x + 1) as u16;
-                    }
+
+                self.increment_index_after_store_load(register(x));
+            }
+            Instruction::Unknown(opcode) => {
+                self.program_counter = address;
+                return Err(Fault::UnknownInstruction(opcode));
+            }
+            Instruction::CycleBackground => {
+                if let Some(colors) = &mut self.colors {
+                    colors.cycle_background();
                 }
-                0x65 => {
-                    self.memory[self.index_register as usize..=self.index_register as usize + x]
-                        .iter()
-                        .enumerate()
-                        .for_each(|(i, r)| {
-                            self.variable_registers[i] = *r;
-                        });
-
-                    if STORE_LOAD_INCREMENTS_I {
-                        self.index_register += (x + 1) as u16;
-                    }
+            }
+            Instruction::AddNibbles(x, y) => {
+                // Each nibble wraps at 8 on its own, which moves both
+                // coordinates of a colour zone at once.
+                let (vx, vy) = (
+                    self.variable_registers[register(x)],
+                    self.variable_registers[register(y)],
+                );
+                self.variable_registers[register(x)] = ((vx & 0x77) + (vy & 0x77)) & 0x77;
+            }
+            Instruction::SetZoneColor(x, y) => {
+                let horizontal = self.variable_registers[register(x)];
+                let vertical = self.variable_registers[(register(x) + 1) % 16];
+                let color = self.variable_registers[register(y)];
+                if let Some(colors) = &mut self.colors {
+                    colors.set_zones(horizontal, vertical, color);
                 }
-                _ => panic!("{:#06X}: unknown instruction!", instruction),
-            },
-            _ => panic!("{:#06X}: unknown instruction!", instruction),
+            }
+            Instruction::SetRowColor(x, y, n) => {
+                let left = self.variable_registers[register(x)];
+                let top = self.variable_registers[(register(x) + 1) % 16];
+                let color = self.variable_registers[register(y)];
+                if let Some(colors) = &mut self.colors {
+                    colors.set_rows(left, top, n, color);
+                }
+            }
+            Instruction::Output(x) => self.output_port = Some(self.variable_registers[register(x)]),
+            Instruction::Input(x) => {
+                self.variable_registers[register(x)] = self.input_port.take().unwrap_or(0)
+            }
         }
+
+        Ok(())
+    }
+}
+
+/// VIP memory as the 1802 sees it: the 4KB of RAM repeated up to the monitor
+/// ROM, and the ROM. Only the digits are kept of the ROM, in the interpreter's
+/// page, where the ROM's addresses wrap to.
+struct VipBus<'a>(&'a mut Chip8);
+
+impl Bus for VipBus<'_> {
+    fn read(&mut self, address: u16) -> u8 {
+        self.0.memory[address as usize % MEMORY_SIZE]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if address < VIP_MONITOR_ADDRESS {
+            self.0.write(address as usize, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{consts::VIP_WORK_AREA_ADDRESS, font::FontPreset};
+
+    #[test]
+    fn test_load_program_at_address() {
+        let mut chip8 = Chip8::new();
+        chip8.load_program_at(&[0x12, 0x34], 0x600).unwrap();
+
+        assert_eq!(chip8.program_counter(), 0x600);
+        assert_eq!(chip8.memory()[0x600..0x602], [0x12, 0x34]);
+    }
+
+    #[test]
+    fn test_load_program_errors() {
+        let mut chip8 = Chip8::new();
+
+        assert!(matches!(chip8.load_program(&[]), Err(LoadError::Empty)));
+        assert!(matches!(
+            chip8.load_program(&[0; 3585]),
+            Err(LoadError::TooLarge {
+                size: 3585,
+                max: 3584
+            })
+        ));
+        assert!(chip8.load_program(&[0; 3584]).is_ok());
+        assert!(chip8.load_program_at(&[0; 2561], 0x600).is_err());
+    }
+
+    #[test]
+    fn test_key_wait_needs_press_and_release() {
+        // V0 = key; ST = V0 (loop forever)
+        let mut chip8 = Chip8::new();
+        chip8
+            .load_program(&[0xF0, 0x0A, 0xF0, 0x18, 0x12, 0x04])
+            .unwrap();
+        chip8.delay_timer = 10;
+
+        chip8.cycle().unwrap();
+        chip8.press_key(0x7);
+        chip8.cycle().unwrap();
+        chip8.vblank();
+        assert_eq!(chip8.program_counter(), 0x200);
+        assert_eq!(chip8.delay_timer, 9);
+
+        chip8.release_key(0x7);
+        chip8.cycle().unwrap();
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.registers()[0], 0x7);
+        assert_eq!(chip8.sound_timer, 0x7);
+    }
+
+    #[test]
+    fn test_release_all_keys() {
+        let mut chip8 = Chip8::new();
+        chip8.load_program(&[0xF3, 0x0A, 0x12, 0x02]).unwrap();
+
+        chip8.cycle().unwrap();
+        chip8.press_key(0x4);
+        chip8.release_all_keys();
+        assert!(chip8.keypad.iter().all(|&down| !down));
+        chip8.release_key(0x4);
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.program_counter(), 0x200);
+
+        chip8.press_key(0x5);
+        chip8.release_key(0x5);
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.registers()[3], 0x5);
+    }
+
+    #[test]
+    fn test_key_wait_on_held_key() {
+        let mut chip8 = Chip8::new();
+        chip8.load_program(&[0xF3, 0x0A, 0x12, 0x02]).unwrap();
+
+        chip8.press_key(0xB);
+        chip8.cycle().unwrap();
+        chip8.press_key(0x2);
+        chip8.release_key(0x2);
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.program_counter(), 0x200);
+
+        chip8.release_key(0xB);
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.registers()[3], 0xB);
+        assert_eq!(chip8.program_counter(), 0x202);
+    }
+
+    #[test]
+    fn test_self_modifying_code() {
+        // V2 = 1; I = 0x200; V0, V1 = 0x62, 0x05; store them over the first
+        // instruction, making it V2 = 5; jump back to it
+        let mut chip8 = Chip8::new();
+        chip8
+            .load_program(&[
+                0x62, 0x01, 0xA2, 0x00, 0x60, 0x62, 0x61, 0x05, 0xF1, 0x55, 0x12, 0x00,
+            ])
+            .unwrap();
+
+        for _ in 0..6 {
+            chip8.cycle().unwrap();
+        }
+        assert_eq!(chip8.registers()[2], 1);
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.registers()[2], 5);
+    }
+
+    #[test]
+    fn test_faults() {
+        let fault = |program: &[u8]| {
+            let mut chip8 = Chip8::new();
+            chip8.load_program(program).unwrap();
+            let fault = (0..100).find_map(|_| chip8.cycle().err());
+            (fault, chip8.program_counter())
+        };
+
+        assert_eq!(fault(&[0x00, 0xEE]), (Some(Fault::StackUnderflow), 0x200));
+        assert_eq!(fault(&[0x22, 0x00]), (Some(Fault::StackOverflow), 0x200));
+        assert_eq!(
+            fault(&[0x60, 0x00, 0xE0, 0x00]),
+            (Some(Fault::UnknownInstruction(0xE000)), 0x202)
+        );
+    }
+
+    #[test]
+    fn test_stack_depth() {
+        // Calls itself forever.
+        let mut chip8 = Chip8::new();
+        chip8.stack_depth = 12;
+        chip8.load_program(&[0x22, 0x00]).unwrap();
+
+        for _ in 0..12 {
+            chip8.cycle().unwrap();
+        }
+        assert_eq!(chip8.cycle(), Err(Fault::StackOverflow));
+        assert_eq!(chip8.stack().len(), 12);
+    }
+
+    #[test]
+    fn test_memory_stack() {
+        // Call 0x204 and loop; there, I = 0xECE; V0, V1 = 0x02, 0x10; store
+        // them over the return address; return to 0x210 instead
+        let mut chip8 = Chip8::new();
+        chip8.memory_stack = true;
+        chip8
+            .load_program(&[
+                0x22, 0x04, 0x12, 0x02, 0xAE, 0xCE, 0x60, 0x02, 0x61, 0x10, 0xF1, 0x55, 0x00, 0xEE,
+            ])
+            .unwrap();
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.memory()[0xECE..0xED0], [0x02, 0x02]);
+
+        for _ in 0..5 {
+            chip8.cycle().unwrap();
+        }
+        assert_eq!(chip8.program_counter(), 0x210);
+    }
+
+    #[test]
+    fn test_memory_stack_depth() {
+        // Calls itself forever.
+        let mut chip8 = Chip8::new();
+        chip8.stack_depth = 255;
+        chip8.memory_stack = true;
+        chip8.load_program(&[0x22, 0x00]).unwrap();
+        let below = chip8.memory()[..VIP_WORK_AREA_ADDRESS].to_vec();
+
+        for _ in 0..24 {
+            chip8.cycle().unwrap();
+        }
+        assert_eq!(chip8.cycle(), Err(Fault::StackOverflow));
+        assert_eq!(chip8.stack().len(), 24);
+        assert_eq!(chip8.memory()[..VIP_WORK_AREA_ADDRESS], below[..]);
+    }
+
+    #[test]
+    fn test_vip_layout() {
+        // V0 = 0x2A; V3 = 0; I = digit 0; draw it; I = second display row;
+        // V0 = 0xFF; store it there; I = V5; V0 = 0x77; store it there
+        let mut chip8 = Chip8::new();
+        chip8.quirks.display_wait = false;
+        chip8.set_layout(Layout::Vip);
+        chip8
+            .load_program(&[
+                0x60, 0x2A, 0x63, 0x00, 0xF3, 0x29, 0xD3, 0x35, 0xAF, 0x08, 0x60, 0xFF, 0xF0, 0x55,
+                0xAE, 0xF5, 0x60, 0x77, 0xF0, 0x55,
+            ])
+            .unwrap();
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.memory()[0xEF0], 0x2A);
+
+        for _ in 0..3 {
+            chip8.cycle().unwrap();
+        }
+        assert_eq!(chip8.index_register(), 0x110);
+        assert_eq!(chip8.memory()[0xF00], 0xF0);
+
+        for _ in 0..3 {
+            chip8.cycle().unwrap();
+        }
+        assert_eq!(chip8.display[1][..8], [true; 8]);
+        assert!(!chip8.display[1][8]);
+
+        for _ in 0..3 {
+            chip8.cycle().unwrap();
+        }
+        assert_eq!(chip8.registers()[5], 0x77);
+
+        // Programs have to end before the interpreter's work area.
+        assert!(chip8.load_program(&[0; 0xCA1]).is_err());
+        assert!(chip8.load_program(&[0; 0xCA0]).is_ok());
+    }
+
+    #[test]
+    fn test_machine_routines() {
+        // V0 = 0x21; call the machine code at 0x208 and loop. There, with R7
+        // at V0: V0 <<= 1; I = 0x334; fill the first byte of the second
+        // display row; return to the interpreter.
+        let program = [
+            0x60, 0x21, 0x02, 0x08, 0x12, 0x04, 0x00, 0x00, 0xE7, 0xF0, 0xFE, 0x57, 0xF8, 0x34,
+            0xAA, 0xF8, 0x03, 0xBA, 0xF8, 0x08, 0xAB, 0xF8, 0xFF, 0x5B, 0xD4,
+        ];
+
+        let mut chip8 = Chip8::new();
+        chip8.set_layout(Layout::Vip);
+        chip8.load_program(&program).unwrap();
+        chip8.run(2).unwrap();
+        assert_eq!(chip8.registers()[0], 0x42);
+        assert_eq!(chip8.memory()[0xEF0], 0x42);
+        assert_eq!(chip8.index_register(), 0x334);
+        assert_eq!(chip8.display[1][..8], [true; 8]);
+        assert_eq!(chip8.program_counter(), 0x204);
+
+        // Without the VIP's memory layout they're skipped.
+        let mut chip8 = Chip8::new();
+        chip8.load_program(&program).unwrap();
+        chip8.run(2).unwrap();
+        assert_eq!(chip8.registers()[0], 0x21);
+        assert_eq!(chip8.program_counter(), 0x204);
+
+        // Machine code branching to itself never returns.
+        let mut chip8 = Chip8::new();
+        chip8.set_layout(Layout::Vip);
+        chip8.load_program(&[0x02, 0x02, 0x30, 0x02]).unwrap();
+        assert_eq!(chip8.cycle(), Err(Fault::RunawayMachineRoutine(0x202)));
+        assert_eq!(chip8.program_counter(), 0x200);
+    }
+
+    #[test]
+    fn test_fonts() {
+        // V0 = 3; I = small 3; I = big 3
+        let program = [0x60, 0x03, 0xF0, 0x29, 0xF0, 0x30];
+
+        let mut chip8 = Chip8::new();
+        chip8.set_font(Font::preset(FontPreset::Schip));
+        chip8.set_layout(Layout::Vip);
+        chip8.load_program(&program).unwrap();
+        chip8.run(2).unwrap();
+        assert_eq!(chip8.index_register(), 0x110 + 15);
+        assert_eq!(
+            chip8.memory()[0x110 + 15..0x110 + 20],
+            [0xF0, 0x10, 0xF0, 0x10, 0xF0]
+        );
+        chip8.run(1).unwrap();
+        assert_eq!(chip8.index_register(), 0x110 + 80 + 30);
+        assert_eq!(
+            chip8.memory()[0x110 + 80 + 30..0x110 + 80 + 40],
+            [0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C]
+        );
+
+        // Without big digits there's nothing for FX30 to point at, and
+        // switching fonts doesn't leave the old ones behind.
+        let mut chip8 = Chip8::new();
+        chip8.set_font(Font::preset(FontPreset::Octo));
+        chip8.set_font(Font::preset(FontPreset::Vip));
+        assert!(chip8.memory()[0x50 + 80..0x200]
+            .iter()
+            .all(|&byte| byte == 0));
+        // V0 = 7; I = small 7; I = big 7
+        chip8
+            .load_program(&[0x60, 0x07, 0xF0, 0x29, 0xF0, 0x30])
+            .unwrap();
+        assert_eq!(chip8.run(3), Err(Fault::UnknownInstruction(0xF030)));
+        assert_eq!(chip8.program_counter(), 0x204);
+        assert_eq!(chip8.index_register(), 0x50 + 35);
+        assert_eq!(
+            chip8.memory()[0x50 + 35..0x50 + 40],
+            [0xF0, 0x10, 0x10, 0x10, 0x10]
+        );
+    }
+
+    #[test]
+    fn test_chip8x() {
+        // V0, V1 = 0x11, 0x72; V0 += V1 by nibble; background; colour the
+        // zone at V0, V1 in V2 = 4; skip if second key V3 = 5; out V2; in V4
+        let mut chip8 = Chip8::new();
+        chip8.set_variant(Variant::Chip8x);
+        chip8
+            .load_program(&[
+                0x60, 0x11, 0x61, 0x72, 0x50, 0x11, 0x02, 0xA0, 0x62, 0x04, 0xB0, 0x20, 0x63, 0x05,
+                0xE3, 0xF2, 0x00, 0x00, 0xF2, 0xF8, 0xF4, 0xFB,
+            ])
+            .unwrap();
+        chip8.second_keypad[5] = true;
+
+        chip8.run(3).unwrap();
+        assert_eq!(chip8.registers()[0], 0x03);
+        chip8.run(4).unwrap();
+        let colors = chip8.colors().unwrap();
+        assert_eq!(colors.background, 1);
+        // One zone across from the fourth, 8 zones down from the third,
+        // wrapping all the way around.
+        assert!((0..32).all(|y| colors.foreground(24, y) == 4));
+        assert_eq!(colors.foreground(16, 8), 1);
+        assert_eq!(colors.foreground(32, 8), 1);
+
+        // Skips the 0000.
+        chip8.run(2).unwrap();
+        assert_eq!(chip8.program_counter(), 0x214);
+        assert_eq!(chip8.output_port, Some(4));
+
+        // Reads 0 with nothing on the port, and goes on.
+        chip8.variable_registers[4] = 0xFF;
+        chip8.run(1).unwrap();
+        assert_eq!(chip8.registers()[4], 0);
+        assert_eq!(chip8.program_counter(), 0x216);
+        chip8.input_port = Some(0x42);
+        chip8.program_counter = 0x214;
+        chip8.run(1).unwrap();
+        assert_eq!(chip8.registers()[4], 0x42);
+        assert_eq!(chip8.input_port, None);
+
+        // Without the variant, BXYN is still BNNN.
+        chip8.set_variant(Variant::Chip8);
+        assert!(chip8.colors().is_none());
+        assert_eq!(chip8.decoded(0x20A), Instruction::JumpWithOffset(0x020));
+    }
+
+    #[test]
+    fn test_hires() {
+        // The header and a patch that isn't CHIP-8 code, then at 0x2C0:
+        // I = font '0'; V0 = 60; draw '0' at (V0, V0); clear; loop forever
+        let mut program = vec![0x12, 0x60];
+        program.resize(HIRES_START_ADDRESS - PROGRAM_START_ADDRESS, 0xFF);
+        program.extend([0xA0, 0x50, 0x60, 0x3C, 0xD0, 0x05, 0x02, 0x30, 0x12, 0xC8]);
+
+        let mut chip8 = Chip8::new();
+        chip8.quirks.display_wait = false;
+        chip8.load_program(&program).unwrap();
+        assert_eq!(chip8.variant(), Variant::Hires);
+        assert_eq!(chip8.program_counter(), HIRES_START_ADDRESS as u16);
+        assert_eq!(chip8.display.len(), 64);
+
+        chip8.run(3).unwrap();
+        assert_eq!(chip8.display[60][60..64], [true; 4]);
+        assert!(chip8.display[0].iter().all(|&pixel| !pixel));
+        chip8.run(1).unwrap();
+        assert!(chip8.display.iter().flatten().all(|&pixel| !pixel));
+
+        // Only plain CHIP-8 programs at 0x200 are taken for hi-res ones.
+        let mut chip8 = Chip8::new();
+        chip8.load_program_at(&program, 0x600).unwrap();
+        assert_eq!(chip8.variant(), Variant::Chip8);
+        let mut chip8 = Chip8::new();
+        chip8.set_variant(Variant::Chip8x);
+        chip8.load_program(&program).unwrap();
+        assert_eq!(chip8.variant(), Variant::Chip8x);
+        assert_eq!(chip8.display.len(), 32);
+    }
+
+    #[test]
+    fn test_addresses_wrap() {
+        // I = 0xFFE; V0 = 0x13; V2 = 0x50; store V0-V2 over the end and start
+        // of memory; I = 0xFFF; load V0-V1 back; skip if key 0 (V1's low
+        // nibble) is down
+        let mut chip8 = Chip8::new();
+        chip8
+            .load_program(&[
+                0xAF, 0xFE, 0x60, 0x13, 0x62, 0x50, 0xF2, 0x55, 0xAF, 0xFF, 0xF1, 0x65, 0xE1, 0x9E,
+            ])
+            .unwrap();
+        chip8.press_key(0x0);
+
+        for _ in 0..7 {
+            chip8.cycle().unwrap();
+        }
+        assert_eq!(chip8.memory()[0xFFE..], [0x13, 0x00]);
+        assert_eq!(chip8.memory()[0], 0x50);
+        assert_eq!(chip8.registers()[0..2], [0x00, 0x50]);
+        assert_eq!(chip8.program_counter(), 0x210);
+
+        // Running off the end of memory carries on at the start.
+        chip8.load_program_at(&[0x60, 0x01], 0xFFE).unwrap();
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.program_counter(), 0x000);
+    }
+
+    #[test]
+    fn test_random_programs_do_not_panic() {
+        let mut rng = StdRng::seed_from_u64(45);
+
+        for _ in 0..200 {
+            let program: Vec<u8> = (0..rng.random_range(2..0x200))
+                .map(|_| rng.random())
+                .collect();
+            let mut chip8 = Chip8::new();
+            chip8.quirks.display_wait = false;
+            chip8.load_program(&program).unwrap();
+
+            for _ in 0..1000 {
+                if chip8.cycle().is_err() {
+                    break;
+                }
+                assert!((chip8.program_counter() as usize) < MEMORY_SIZE);
+                assert!(chip8.stack().len() <= STACK_DEPTH);
+            }
+        }
+    }
+
+    #[test]
+    fn test_load_program_from_reader() {
+        let mut chip8 = Chip8::new();
+        chip8.load_program_from(&[0x00, 0xE0][..], 0x200).unwrap();
+
+        assert_eq!(chip8.memory()[0x200..0x202], [0x00, 0xE0]);
+        assert!(chip8.load_program_from(io::empty(), 0x200).is_err());
+        assert!(matches!(
+            read_program(io::repeat(0), 0x200),
+            Err(LoadError::TooLarge {
+                size: 3585,
+                max: 3584
+            })
+        ));
     }
 }
@@ -0,0 +1,146 @@
+use crate::{
+    consts::{HEIGHT, WIDTH},
+    palette::Rgb,
+};
+
+/// Pixels across a colour zone. The colour board keeps one colour for each
+/// 8 pixels of a row.
+pub const ZONE_WIDTH: usize = 8;
+/// Rows in a `BXY0` zone.
+pub const ZONE_HEIGHT: usize = 4;
+const COLUMNS: usize = WIDTH / ZONE_WIDTH;
+
+/// Foreground colours by number: red, blue and green in bits 0, 1 and 2.
+pub const FOREGROUND_COLORS: [Rgb; 8] = [
+    [0x00, 0x00, 0x00],
+    [0xFF, 0x00, 0x00],
+    [0x00, 0x00, 0xFF],
+    [0xFF, 0x00, 0xFF],
+    [0x00, 0xFF, 0x00],
+    [0xFF, 0xFF, 0x00],
+    [0x00, 0xFF, 0xFF],
+    [0xFF, 0xFF, 0xFF],
+];
+/// Background colours in the order `02A0` cycles through them.
+pub const BACKGROUND_COLORS: [Rgb; 4] = [
+    [0x00, 0x00, 0x80],
+    [0x00, 0x00, 0x00],
+    [0x00, 0x80, 0x00],
+    [0x80, 0x00, 0x00],
+];
+
+/// The VP-590 colour board: one background colour, and a foreground colour
+/// for each 8 pixels of each row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorBoard {
+    /// Index into [`BACKGROUND_COLORS`].
+    pub background: u8,
+    rows: [[u8; COLUMNS]; HEIGHT],
+}
+
+impl Default for ColorBoard {
+    /// Red on dark blue, as the interpreter starts.
+    fn default() -> Self {
+        ColorBoard {
+            background: 0,
+            rows: [[1; COLUMNS]; HEIGHT],
+        }
+    }
+}
+
+impl ColorBoard {
+    /// `02A0`
+    pub fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUND_COLORS.len() as u8;
+    }
+
+    /// `BXY0`: the low nibbles of `horizontal` and `vertical` are the first
+    /// zone across and down, the high ones how many more zones to colour in
+    /// each direction. Zones are 8x4 pixels and wrap around the screen.
+    pub fn set_zones(&mut self, horizontal: u8, vertical: u8, color: u8) {
+        for zone_y in 0..=(vertical >> 4) as usize {
+            let top = ((vertical & 0xF) as usize + zone_y) * ZONE_HEIGHT;
+            for y in top..top + ZONE_HEIGHT {
+                for zone_x in 0..=(horizontal >> 4) as usize {
+                    let column = (horizontal & 0xF) as usize + zone_x;
+                    self.rows[y % HEIGHT][column % COLUMNS] = color & 7;
+                }
+            }
+        }
+    }
+
+    /// `BXYN`: colours `n` rows of the 8 pixels around `x`, from `y` down.
+    pub fn set_rows(&mut self, x: u8, y: u8, n: u8, color: u8) {
+        let column = x as usize / ZONE_WIDTH % COLUMNS;
+        for row in y as usize..y as usize + n as usize {
+            self.rows[row % HEIGHT][column] = color & 7;
+        }
+    }
+
+    /// Index into [`FOREGROUND_COLORS`] of the pixel at `x`, `y`.
+    pub fn foreground(&self, x: usize, y: usize) -> u8 {
+        self.rows[y % HEIGHT][x / ZONE_WIDTH % COLUMNS]
+    }
+
+    pub fn color(&self, x: usize, y: usize, pixel: bool) -> Rgb {
+        if pixel {
+            FOREGROUND_COLORS[self.foreground(x, y) as usize]
+        } else {
+            BACKGROUND_COLORS[self.background as usize]
+        }
+    }
+}
+
+/// The VP-595 sound board's tone for a value written with `FXF8`: its clock
+/// divided by the value plus one.
+pub fn tone_frequency(value: u8) -> f32 {
+    27_535.0 / (value as f32 + 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_background_cycles() {
+        let mut colors = ColorBoard::default();
+        assert_eq!(colors.color(0, 0, false), BACKGROUND_COLORS[0]);
+
+        for _ in 0..5 {
+            colors.cycle_background();
+        }
+        assert_eq!(colors.color(0, 0, false), BACKGROUND_COLORS[1]);
+    }
+
+    #[test]
+    fn test_zones() {
+        let mut colors = ColorBoard::default();
+        // Two zones across from the last, wrapping to the first; one down
+        // from the second.
+        colors.set_zones(0x17, 0x01, 4);
+
+        for y in 0..HEIGHT {
+            for x in (0..WIDTH).step_by(ZONE_WIDTH) {
+                let expected = if (4..8).contains(&y) && (x == 0 || x == 56) {
+                    4
+                } else {
+                    1
+                };
+                assert_eq!(colors.foreground(x, y), expected, "{x}, {y}");
+            }
+        }
+        assert_eq!(colors.color(63, 7, true), FOREGROUND_COLORS[4]);
+    }
+
+    #[test]
+    fn test_rows() {
+        let mut colors = ColorBoard::default();
+        colors.set_rows(20, 30, 3, 0xF);
+
+        assert_eq!(colors.foreground(16, 30), 7);
+        assert_eq!(colors.foreground(23, 31), 7);
+        assert_eq!(colors.foreground(16, 0), 7);
+        assert_eq!(colors.foreground(16, 1), 1);
+        assert_eq!(colors.foreground(24, 30), 1);
+    }
+}
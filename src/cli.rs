@@ -0,0 +1,656 @@
+use std::{
+    fs, io,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::{
+    asm::assemble,
+    chip8::{check_program_size, read_program},
+    config::{Config, ConfigFile},
+    consts::MEMORY_SIZE,
+    database::{sha1_hex, Database},
+    debugger,
+    disasm::disassemble_program,
+    emulator::Emulator,
+    filters::Filters,
+    font::Font,
+    headless::Headless,
+    keymap::Keymap,
+    layout::Layout,
+    palette::{parse_color, Palette},
+    quirks::Quirks,
+    recorder::Recorder,
+    roms, screenshot,
+    settings::{FastForward, Settings},
+    timing::Timing,
+    variant::Variant,
+};
+
+#[derive(Parser)]
+#[command(name = "chip-8", version, about = "A CHIP-8 emulator")]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// ROM to run when no subcommand is given
+    rom: Option<PathBuf>,
+
+    /// ROM to pick from a zip archive or directory, by name or number
+    #[arg(long, value_name = "NAME|N")]
+    select: Option<String>,
+
+    #[command(flatten)]
+    options: Options,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a ROM in a window
+    Run {
+        #[command(flatten)]
+        rom: RomArgs,
+        #[command(flatten)]
+        options: Options,
+        /// Stop after this many instructions
+        #[arg(long)]
+        cycles: Option<u32>,
+    },
+    /// Print the instructions of a ROM
+    Disasm {
+        #[command(flatten)]
+        rom: RomArgs,
+        /// Address the ROM is loaded at, in hex
+        #[arg(long, value_name = "ADDRESS", value_parser = parse_address, default_value = "200")]
+        load_address: usize,
+    },
+    /// Assemble a source file into a ROM
+    Asm {
+        source: PathBuf,
+        /// Where to write the ROM
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Address the ROM is loaded at, in hex
+        #[arg(long, value_name = "ADDRESS", value_parser = parse_address, default_value = "200")]
+        load_address: usize,
+    },
+    /// Print the size, hash and database entry of a ROM
+    Info {
+        #[command(flatten)]
+        rom: RomArgs,
+    },
+    /// Run a ROM without a window for a fixed number of frames
+    Headless {
+        #[command(flatten)]
+        rom: RomArgs,
+        #[command(flatten)]
+        options: Options,
+        /// Number of 60Hz frames to run
+        #[arg(long, default_value_t = 600)]
+        frames: u32,
+        /// Record the run as a GIF
+        #[arg(long, group = "recording")]
+        gif: Option<PathBuf>,
+        /// Record the run as a Y4M video with a WAV file next to it
+        #[arg(long, group = "recording")]
+        video: Option<PathBuf>,
+        /// Record the beeper as a WAV file
+        #[arg(long, group = "recording")]
+        wav: Option<PathBuf>,
+        /// Save a PNG of the last frame
+        #[arg(long)]
+        screenshot: Option<PathBuf>,
+    },
+    /// Step through a ROM from an interactive prompt
+    Debug {
+        #[command(flatten)]
+        rom: RomArgs,
+        #[command(flatten)]
+        options: Options,
+    },
+    /// Print the configuration a ROM would run with, e.g. for bug reports
+    Config {
+        #[command(flatten)]
+        rom: RomArgs,
+        #[command(flatten)]
+        options: Options,
+    },
+}
+
+/// Where to find a ROM.
+#[derive(Args)]
+struct RomArgs {
+    /// ROM file, zip archive or directory of ROMs, or - for stdin
+    rom: PathBuf,
+    /// ROM to pick from a zip archive or directory, by name or number
+    #[arg(long, value_name = "NAME|N")]
+    select: Option<String>,
+}
+
+/// A ROM read into memory.
+struct Rom {
+    /// Keys the per-ROM keymap and config tables.
+    name: String,
+    /// Where the ROM came from, for error messages.
+    label: String,
+    program: Vec<u8>,
+}
+
+/// Settings that can be changed from the command line. Anything not given
+/// comes from the config file, the ROM database and the keymap file.
+#[derive(Args)]
+struct Options {
+    /// Use the quirks and speed of a platform, e.g. originalChip8 or superchip
+    #[arg(long, value_name = "PLATFORM")]
+    quirks: Option<String>,
+    /// Set a single quirk, e.g. --quirk wrap=true
+    #[arg(long, value_name = "NAME=BOOL", value_parser = parse_quirk)]
+    quirk: Vec<(String, bool)>,
+    /// Instructions per second
+    #[arg(long, value_name = "IPS")]
+    speed: Option<u32>,
+    /// Instruction timing: fixed instructions per frame, or as on the COSMAC VIP
+    #[arg(long, value_name = "fixed|vip")]
+    timing: Option<Timing>,
+    /// Subroutines that can be nested, e.g. 12 as on the COSMAC VIP
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(1..))]
+    stack_depth: Option<u8>,
+    /// Keep return addresses in memory at 0xEA0-0xECF, as on the COSMAC VIP,
+    /// which limits the stack depth to 24
+    #[arg(long)]
+    memory_stack: bool,
+    /// Interpreter memory layout: just the font, or the COSMAC VIP's stack,
+    /// registers and display in memory
+    #[arg(long, value_name = "minimal|vip")]
+    layout: Option<Layout>,
+    /// Digit font: octo, vip, dream6800, eti660, schip, or a file with the 80
+    /// bytes of the small digits and optionally the big ones after them
+    #[arg(long, value_name = "NAME|FILE", value_parser = Font::open)]
+    font: Option<Font>,
+    /// Instruction set: plain CHIP-8, CHIP-8X with colours, a second keypad
+    /// and an I/O port, loaded at 0x300, or 64x64 hi-res CHIP-8, which is
+    /// otherwise picked for programs starting with 1260
+    #[arg(long, value_name = "chip8|chip8x|hires")]
+    variant: Option<Variant>,
+    /// Window and recording scale
+    #[arg(long)]
+    scale: Option<NonZeroUsize>,
+    /// Background and foreground colors, e.g. #000000,#ffffff
+    #[arg(long, value_name = "BG,FG", value_parser = parse_palette)]
+    palette: Option<Palette>,
+    /// Seed for the random number generator
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Address programs are loaded at, in hex, e.g. 600 for ETI-660 programs
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address)]
+    load_address: Option<usize>,
+    /// Folder or zip archive listed by the in-window ROM menu (F8)
+    #[arg(long, value_name = "PATH")]
+    rom_dir: Option<PathBuf>,
+    /// Fast-forward multiplier, e.g. 4, or uncapped
+    #[arg(long, value_name = "N|uncapped")]
+    fast_forward: Option<FastForward>,
+    /// Keymap preset (qwerty, azerty, dvorak) or keymap file
+    #[arg(long, value_name = "PRESET|FILE")]
+    keymap: Option<String>,
+    /// Config file to use instead of the one in the config directory
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+fn parse_quirk(s: &str) -> Result<(String, bool), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=BOOL, found '{s}'"))?;
+    let value = value
+        .parse()
+        .map_err(|_| format!("'{value}' is not true or false"))?;
+
+    // Catch typos while parsing rather than after the ROM is loaded.
+    Quirks::default().set(name, value)?;
+
+    Ok((name.to_string(), value))
+}
+
+fn parse_address(s: &str) -> Result<usize, String> {
+    let digits = s.trim_start_matches("0x").trim_start_matches("0X");
+
+    usize::from_str_radix(digits, 16)
+        .ok()
+        .filter(|&address| address < MEMORY_SIZE)
+        .ok_or_else(|| format!("'{s}' is not an address"))
+}
+
+fn parse_palette(s: &str) -> Result<Palette, String> {
+    let (background, foreground) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected BG,FG, found '{s}'"))?;
+    let color = |color: &str| parse_color(color).ok_or_else(|| format!("invalid color '{color}'"));
+
+    Ok(Palette::new(color(background)?, color(foreground)?))
+}
+
+impl Options {
+    /// Builds the settings for `program`, from lowest to highest priority:
+    /// defaults, the keymap file, the ROM database, the global config, the
+    /// config for this ROM, then the command line.
+    fn settings(&self, program: &[u8], rom_name: &str) -> Result<Settings, String> {
+        let keymap = Keymap::load(rom_name).unwrap_or_else(|e| {
+            eprintln!("failed to load keymap, using the default one: {e}");
+            Keymap::default()
+        });
+
+        let mut settings = Settings {
+            keymap,
+            ..Default::default()
+        };
+
+        let database = Database::load();
+        let info = database.lookup(program);
+        if let Some(info) = &info {
+            eprintln!(
+                "{} by {} ({})",
+                info.title,
+                info.authors.join(", "),
+                info.platform.as_deref().unwrap_or("unknown platform")
+            );
+            settings.apply_rom_info(info);
+        }
+
+        let config = match &self.config {
+            Some(path) => {
+                ConfigFile::from_path(path).map_err(|e| format!("{}: {e}", path.display()))?
+            }
+            None => ConfigFile::load().map_err(|e| format!("config file: {e}"))?,
+        };
+        config
+            .apply(&mut settings, rom_name)
+            .map_err(|e| format!("config file: {e}"))?;
+
+        if let Some(keymap) = &self.keymap {
+            settings.keymap = match Keymap::preset(keymap) {
+                Some(preset) => preset,
+                None => {
+                    let contents =
+                        fs::read_to_string(keymap).map_err(|e| format!("{keymap}: {e}"))?;
+                    Keymap::from_toml(&contents, rom_name).map_err(|e| format!("{keymap}: {e}"))?
+                }
+            };
+            if let Some(info) = &info {
+                settings.keymap.apply_hints(&info.keys);
+            }
+        }
+        if let Some(id) = &self.quirks {
+            let platform = database
+                .platform(id)
+                .ok_or_else(|| format!("unknown platform '{id}'"))?;
+            settings.quirks = Quirks::default();
+            settings.quirks.apply(&platform.quirks);
+            settings.instructions_per_second = platform.default_tickrate * 60;
+            settings.stack_depth = platform.stack_depth();
+            settings.font = Font::preset(platform.font());
+            settings.set_variant(platform.variant());
+        }
+        for (name, value) in &self.quirk {
+            settings.quirks.set(name, *value)?;
+        }
+        if let Some(speed) = self.speed {
+            settings.instructions_per_second = speed;
+        }
+        if let Some(timing) = self.timing {
+            settings.timing = timing;
+        }
+        if let Some(stack_depth) = self.stack_depth {
+            settings.stack_depth = stack_depth as usize;
+        }
+        if self.memory_stack {
+            settings.memory_stack = true;
+        }
+        if let Some(layout) = self.layout {
+            settings.layout = layout;
+        }
+        if let Some(font) = &self.font {
+            settings.font = font.clone();
+        }
+        if let Some(variant) = self.variant {
+            settings.set_variant(variant);
+        }
+        if let Some(scale) = self.scale {
+            settings.scale = scale.get();
+        }
+        if let Some(palette) = self.palette {
+            settings.palette = palette;
+        }
+        if let Some(seed) = self.seed {
+            settings.seed = Some(seed);
+        }
+        if let Some(load_address) = self.load_address {
+            settings.load_address = load_address;
+        }
+        if let Some(rom_dir) = &self.rom_dir {
+            settings.rom_dir = Some(rom_dir.clone());
+        }
+        if let Some(fast_forward) = self.fast_forward {
+            settings.fast_forward = fast_forward;
+        }
+
+        Ok(settings)
+    }
+}
+
+impl RomArgs {
+    /// Where the ROM menu looks when no ROM folder is configured: the archive
+    /// or directory the ROM came from, or the folder the ROM file is in.
+    fn library(&self) -> Option<PathBuf> {
+        if self.rom == Path::new("-") {
+            None
+        } else if self.rom.is_dir() || roms::is_zip(&self.rom) {
+            Some(self.rom.clone())
+        } else {
+            match self.rom.parent() {
+                Some(parent) if parent != Path::new("") => Some(parent.to_path_buf()),
+                _ => Some(PathBuf::from(".")),
+            }
+        }
+    }
+
+    /// Reads the ROM from stdin when the path is `-`, and otherwise from the
+    /// file, or from one picked out of a zip archive or directory. Whether it
+    /// fits in memory depends on the load address, so that is only checked
+    /// when it is loaded.
+    fn open(&self) -> Result<Rom, String> {
+        if self.rom == Path::new("-") {
+            let program = read_program(io::stdin().lock(), 0).map_err(|e| format!("stdin: {e}"))?;
+
+            return Ok(Rom {
+                name: "stdin".to_string(),
+                label: "stdin".to_string(),
+                program,
+            });
+        }
+
+        let entries = roms::list(&self.rom)?;
+        let entry = roms::choose(
+            &entries,
+            self.select.as_deref(),
+            &mut io::stdin().lock(),
+            // Keep stdout for the output of `disasm` and `info`.
+            &mut io::stderr(),
+        )
+        .map_err(|e| format!("{}: {e}", self.rom.display()))?;
+
+        Ok(Rom {
+            name: entry.rom_name(),
+            label: entry.name.clone(),
+            program: entry.read()?,
+        })
+    }
+}
+
+impl Cli {
+    pub fn run(self) -> Result<(), String> {
+        match self.command {
+            Some(command) => command.run(),
+            None => match self.rom {
+                Some(rom) => Command::Run {
+                    rom: RomArgs {
+                        rom,
+                        select: self.select,
+                    },
+                    options: self.options,
+                    cycles: None,
+                }
+                .run(),
+                None => Err("no ROM given, see --help".to_string()),
+            },
+        }
+    }
+}
+
+impl Command {
+    fn run(self) -> Result<(), String> {
+        match self {
+            Command::Run {
+                rom,
+                options,
+                cycles,
+            } => {
+                let library = rom.library();
+                let rom = rom.open()?;
+
+                // ROMs picked from the menu get their settings the same way.
+                let settings_for = move |program: &[u8], rom_name: &str| {
+                    let mut settings = options.settings(program, rom_name)?;
+                    if settings.rom_dir.is_none() {
+                        settings.rom_dir = library.clone();
+                    }
+                    Ok(settings)
+                };
+                let settings = settings_for(&rom.program, &rom.name)?;
+
+                let mut emulator = Emulator::new(&rom.program, &rom.name, settings)
+                    .map_err(|e| format!("{}: {e}", rom.label))?
+                    .with_settings_for(Box::new(settings_for));
+                emulator.run(cycles.unwrap_or(u32::MAX));
+            }
+            Command::Disasm { rom, load_address } => {
+                let rom = rom.open()?;
+
+                for line in disassemble_program(&rom.program, load_address) {
+                    println!("{line}");
+                }
+            }
+            Command::Asm {
+                source,
+                output,
+                load_address,
+            } => {
+                let contents = fs::read_to_string(&source)
+                    .map_err(|e| format!("{}: {e}", source.display()))?;
+                let program = assemble(&contents, load_address)
+                    .map_err(|e| format!("{}: {e}", source.display()))?;
+                check_program_size(program.len(), load_address)
+                    .map_err(|e| format!("{}: {e}", source.display()))?;
+
+                fs::write(&output, program).map_err(|e| format!("{}: {e}", output.display()))?;
+            }
+            Command::Info { rom } => {
+                let Rom { name, program, .. } = rom.open()?;
+
+                println!("name:      {name}");
+                println!("size:      {} bytes", program.len());
+                println!("sha1:      {}", sha1_hex(&program));
+
+                match Database::load().lookup(&program) {
+                    Some(info) => {
+                        println!("title:     {}", info.title);
+                        println!("authors:   {}", info.authors.join(", "));
+                        println!(
+                            "platform:  {}",
+                            info.platform.as_deref().unwrap_or("unknown")
+                        );
+                        if let Some(tickrate) = info.tickrate {
+                            println!("tickrate:  {tickrate}");
+                        }
+                        println!("quirks:    {:?}", info.quirks);
+                    }
+                    None => println!("not in the ROM database"),
+                }
+            }
+            Command::Headless {
+                rom,
+                options,
+                frames,
+                gif,
+                video,
+                wav,
+                screenshot,
+            } => {
+                let rom = rom.open()?;
+                let settings = options.settings(&rom.program, &rom.name)?;
+
+                let chip8 = settings
+                    .load(&rom.program)
+                    .map_err(|e| format!("{}: {e}", rom.label))?;
+                let clock = settings.frame_clock(&chip8);
+                let mut headless = Headless::with_chip8(chip8, clock);
+
+                let recorder = if let Some(path) = &gif {
+                    Some((
+                        path,
+                        Recorder::gif(
+                            path,
+                            &settings.palette,
+                            settings.scale,
+                            headless.chip8.display.len(),
+                        ),
+                    ))
+                } else if let Some(path) = &video {
+                    Some((
+                        path,
+                        Recorder::video(
+                            path,
+                            &settings.palette,
+                            &Filters::default(),
+                            settings.scale,
+                            headless.chip8.display.len(),
+                            &settings.audio,
+                        ),
+                    ))
+                } else {
+                    wav.as_ref()
+                        .map(|path| (path, Recorder::audio(path, &settings.audio)))
+                };
+                if let Some((path, recorder)) = recorder {
+                    let error = |e| format!("{}: {e}", path.display());
+                    headless.record(recorder.map_err(error)?).map_err(error)?;
+                }
+
+                headless.run(frames).map_err(|e| e.to_string())?;
+                headless.stop_recording().map_err(|e| e.to_string())?;
+
+                if let Some(path) = screenshot {
+                    screenshot::save_scaled(
+                        &headless.chip8,
+                        &settings.palette,
+                        &Filters::default(),
+                        settings.scale,
+                        &path,
+                    )
+                    .map_err(|e| format!("{}: {e}", path.display()))?;
+                }
+            }
+            Command::Debug { rom, options } => {
+                let rom = rom.open()?;
+                let settings = options.settings(&rom.program, &rom.name)?;
+
+                let chip8 = settings
+                    .load(&rom.program)
+                    .map_err(|e| format!("{}: {e}", rom.label))?;
+                let clock = settings.frame_clock(&chip8);
+                debugger::run(Headless::with_chip8(chip8, clock))?;
+            }
+            Command::Config { rom, options } => {
+                let rom = rom.open()?;
+                let settings = options.settings(&rom.program, &rom.name)?;
+
+                println!(
+                    "# chip-8 {} effective configuration for {}",
+                    env!("CARGO_PKG_VERSION"),
+                    rom.label
+                );
+                print!("{}", Config::effective(&settings).to_toml());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_cli_is_valid() {
+        use clap::CommandFactory;
+
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_bare_rom_runs() {
+        let cli = Cli::try_parse_from(["chip-8", "pong.ch8", "--speed", "1000"]).unwrap();
+
+        assert!(cli.command.is_none());
+        assert_eq!(cli.rom, Some(PathBuf::from("pong.ch8")));
+        assert_eq!(cli.options.speed, Some(1000));
+    }
+
+    #[test]
+    fn test_options_override_settings() {
+        let cli = Cli::try_parse_from([
+            "chip-8",
+            "run",
+            "pong.ch8",
+            "--quirks",
+            "superchip",
+            "--quirk",
+            "wrap=true",
+            "--palette",
+            "#000,#fff",
+            "--keymap",
+            "dvorak",
+        ])
+        .unwrap();
+        let Some(Command::Run { options, .. }) = cli.command else {
+            panic!("expected the run subcommand");
+        };
+        let settings = options.settings(&[0x12, 0x00], "pong").unwrap();
+
+        assert!(!settings.quirks.shift_use_vy);
+        assert!(settings.quirks.wrap);
+        assert_eq!(settings.instructions_per_second, 30 * 60);
+        assert_eq!(settings.palette, Palette::new([0, 0, 0], [255, 255, 255]));
+        assert_eq!(settings.keymap, Keymap::preset("dvorak").unwrap());
+    }
+
+    #[test]
+    fn test_invalid_options() {
+        assert!(
+            Cli::try_parse_from(["chip-8", "run", "pong.ch8", "--quirk", "warp=true"]).is_err()
+        );
+        assert!(Cli::try_parse_from(["chip-8", "run", "pong.ch8", "--palette", "#000"]).is_err());
+        assert!(Cli::try_parse_from(["chip-8", "run", "pong.ch8", "--scale", "0"]).is_err());
+        assert!(Cli::try_parse_from([
+            "chip-8", "headless", "pong.ch8", "--gif", "a.gif", "--wav", "a.wav"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_read_rom_errors() {
+        let dir = env::temp_dir();
+        let empty = dir.join("chip8-test-empty.ch8");
+        let large = dir.join("chip8-test-large.ch8");
+        fs::write(&empty, []).unwrap();
+        fs::write(&large, vec![0; MEMORY_SIZE + 1]).unwrap();
+
+        let open = |path: PathBuf| {
+            RomArgs {
+                rom: path,
+                select: None,
+            }
+            .open()
+            .map(|rom| rom.program)
+        };
+
+        assert!(open(dir.join("chip8-test-missing.ch8")).is_err());
+        assert!(open(empty).unwrap_err().contains("empty"));
+        assert!(open(large).unwrap_err().contains("fit in memory"));
+    }
+}
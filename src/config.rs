@@ -0,0 +1,373 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    font::Font,
+    keymap::KeymapSection,
+    palette::{format_color, parse_color, Palette},
+    settings::Settings,
+};
+
+/// ```toml
+/// scale = 8
+/// speed = 1000
+///
+/// [palette]
+/// background = "#000000"
+/// foreground = "#ffcc00"
+///
+/// [quirks]
+/// wrap = true
+///
+/// [audio]
+/// waveform = "square"
+///
+/// [keymap]
+/// preset = "azerty"
+///
+/// [roms.pong]
+/// speed = 500
+///
+/// [roms.pong.quirks]
+/// vf_reset = false
+/// ```
+///
+/// The top level applies to every ROM and each `[roms.<name>]` table, keyed
+/// by the ROM's file name without the extension, applies on top of it. The
+/// keymap tables take the same keys as the keymap file.
+#[derive(Debug, Default, PartialEq)]
+pub struct ConfigFile {
+    pub global: Config,
+    pub roms: HashMap<String, Config>,
+}
+
+/// One layer of settings. Anything left out keeps the value from the layers
+/// below.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<usize>,
+    /// Instructions per second
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed: Option<u32>,
+    /// `"fixed"` instructions per frame, or `"vip"` instruction timings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing: Option<String>,
+    /// Subroutines that can be nested, e.g. 12 as on the COSMAC VIP
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack_depth: Option<u8>,
+    /// Keep return addresses in memory where the COSMAC VIP did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_stack: Option<bool>,
+    /// `"minimal"`, or `"vip"` to put the stack, registers and display in
+    /// memory like the COSMAC VIP
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    /// A built-in font such as `"vip"` or `"schip"`, or a font file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    /// `"chip8"`, `"hires"`, or `"chip8x"`, which also loads programs at
+    /// `0x300`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Where programs are loaded, e.g. `0x600` for ETI-660 programs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_address: Option<usize>,
+    /// Folder or zip archive listed by the in-window ROM menu
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rom_dir: Option<PathBuf>,
+    /// Fast-forward multiplier, e.g. `"4x"`, or `"uncapped"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fast_forward: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mute_fast_forward: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub palette: Option<PaletteConfig>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub quirks: BTreeMap<String, bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio: Option<AudioConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keymap: Option<KeymapSection>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PaletteConfig {
+    pub background: String,
+    pub foreground: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AudioConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub waveform: Option<String>,
+}
+
+/// `$CONFIG_DIR/chip-8/config.toml`, e.g. `~/.config/chip-8/config.toml`.
+pub fn config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("chip-8").join("config.toml"))
+}
+
+impl ConfigFile {
+    /// Loads the config file from the user's config directory. Having no file
+    /// is the same as an empty one.
+    pub fn load() -> Result<Self, String> {
+        match config_path().map(fs::read_to_string) {
+            Some(Ok(contents)) => ConfigFile::from_toml(&contents),
+            Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(ConfigFile::default()),
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        ConfigFile::from_toml(&contents)
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, String> {
+        // Split off the ROM tables by hand, since unknown keys are not caught
+        // through `#[serde(flatten)]`.
+        let mut table: toml::Table = toml::from_str(contents).map_err(|e| e.to_string())?;
+        let roms = match table.remove("roms") {
+            Some(roms) => roms.try_into().map_err(|e| format!("roms: {e}"))?,
+            None => HashMap::new(),
+        };
+
+        Ok(ConfigFile {
+            global: table.try_into().map_err(|e| e.to_string())?,
+            roms,
+        })
+    }
+
+    /// Applies the global table and then the table for `rom_name`.
+    pub fn apply(&self, settings: &mut Settings, rom_name: &str) -> Result<(), String> {
+        self.global.apply(settings)?;
+        if let Some(overrides) = self.roms.get(rom_name) {
+            overrides
+                .apply(settings)
+                .map_err(|e| format!("roms.{rom_name}: {e}"))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Config {
+    pub fn apply(&self, settings: &mut Settings) -> Result<(), String> {
+        match self.scale {
+            Some(0) => return Err("scale must be at least 1".to_string()),
+            Some(scale) => settings.scale = scale,
+            None => {}
+        }
+        if let Some(speed) = self.speed {
+            settings.instructions_per_second = speed;
+        }
+        if let Some(timing) = &self.timing {
+            settings.timing = timing.parse()?;
+        }
+        match self.stack_depth {
+            Some(0) => return Err("stack_depth must be at least 1".to_string()),
+            Some(stack_depth) => settings.stack_depth = stack_depth as usize,
+            None => {}
+        }
+        if let Some(memory_stack) = self.memory_stack {
+            settings.memory_stack = memory_stack;
+        }
+        if let Some(layout) = &self.layout {
+            settings.layout = layout.parse()?;
+        }
+        if let Some(font) = &self.font {
+            settings.font = Font::open(font)?;
+        }
+        if let Some(variant) = &self.variant {
+            settings.set_variant(variant.parse()?);
+        }
+        if let Some(seed) = self.seed {
+            settings.seed = Some(seed);
+        }
+        if let Some(load_address) = self.load_address {
+            settings.load_address = load_address;
+        }
+        if let Some(rom_dir) = &self.rom_dir {
+            settings.rom_dir = Some(rom_dir.clone());
+        }
+        if let Some(fast_forward) = &self.fast_forward {
+            settings.fast_forward = fast_forward.parse()?;
+        }
+        if let Some(mute) = self.mute_fast_forward {
+            settings.mute_fast_forward = mute;
+        }
+        if let Some(palette) = &self.palette {
+            let color =
+                |color: &str| parse_color(color).ok_or_else(|| format!("invalid color '{color}'"));
+            settings.palette =
+                Palette::new(color(&palette.background)?, color(&palette.foreground)?);
+        }
+        for (name, value) in &self.quirks {
+            settings.quirks.set(name, *value)?;
+        }
+        if let Some(audio) = &self.audio {
+            if let Some(frequency) = audio.frequency {
+                settings.audio.frequency = frequency;
+            }
+            if let Some(volume) = audio.volume {
+                settings.audio.volume = volume;
+            }
+            if let Some(waveform) = &audio.waveform {
+                settings.audio.waveform = waveform.parse()?;
+            }
+        }
+        if let Some(keymap) = &self.keymap {
+            keymap.apply(&mut settings.keymap)?;
+        }
+
+        Ok(())
+    }
+
+    /// Every setting spelled out, ready to be printed as a config file.
+    pub fn effective(settings: &Settings) -> Self {
+        Config {
+            scale: Some(settings.scale),
+            speed: Some(settings.instructions_per_second),
+            timing: Some(settings.timing.to_string()),
+            stack_depth: Some(settings.stack_depth as u8),
+            memory_stack: Some(settings.memory_stack),
+            layout: Some(settings.layout.to_string()),
+            font: Some(settings.font.name.clone()),
+            variant: Some(settings.variant.to_string()),
+            seed: settings.seed,
+            load_address: Some(settings.load_address),
+            rom_dir: settings.rom_dir.clone(),
+            fast_forward: Some(settings.fast_forward.to_string()),
+            mute_fast_forward: Some(settings.mute_fast_forward),
+            palette: Some(PaletteConfig {
+                background: format_color(settings.palette.background),
+                foreground: format_color(settings.palette.foreground),
+            }),
+            quirks: settings
+                .quirks
+                .named()
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+            audio: Some(AudioConfig {
+                frequency: Some(settings.audio.frequency),
+                volume: Some(settings.audio.volume),
+                waveform: Some(settings.audio.waveform.to_string()),
+            }),
+            keymap: Some(settings.keymap.section()),
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("config is serializable")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        audio::Waveform, font::FontPreset, keymap::Keymap, layout::Layout, settings::FastForward,
+        timing::Timing, variant::Variant,
+    };
+
+    const CONFIG: &str = r##"
+        scale = 8
+        speed = 1000
+
+        [quirks]
+        wrap = true
+
+        [audio]
+        waveform = "square"
+
+        [roms.pong]
+        speed = 500
+
+        [roms.pong.palette]
+        background = "#000"
+        foreground = "#fff"
+    "##;
+
+    #[test]
+    fn test_rom_table_overrides_global() {
+        let config = ConfigFile::from_toml(CONFIG).unwrap();
+
+        let mut tetris = Settings::default();
+        config.apply(&mut tetris, "tetris").unwrap();
+        assert_eq!(tetris.scale, 8);
+        assert_eq!(tetris.instructions_per_second, 1000);
+        assert!(tetris.quirks.wrap);
+        assert_eq!(tetris.audio.waveform, Waveform::Square);
+        assert_eq!(tetris.palette, Palette::default());
+
+        let mut pong = Settings::default();
+        config.apply(&mut pong, "pong").unwrap();
+        assert_eq!(pong.scale, 8);
+        assert_eq!(pong.instructions_per_second, 500);
+        assert_eq!(pong.palette, Palette::new([0, 0, 0], [255, 255, 255]));
+    }
+
+    #[test]
+    fn test_effective_config_round_trips() {
+        let mut settings = Settings {
+            seed: Some(42),
+            load_address: 0x600,
+            rom_dir: Some(PathBuf::from("/roms")),
+            fast_forward: FastForward::Uncapped,
+            timing: Timing::Vip,
+            stack_depth: 12,
+            memory_stack: true,
+            layout: Layout::Vip,
+            font: Font::preset(FontPreset::Schip),
+            variant: Variant::Chip8x,
+            mute_fast_forward: false,
+            keymap: Keymap::preset("azerty").unwrap(),
+            ..Default::default()
+        };
+        settings.quirks.bnnn = true;
+
+        let printed = Config::effective(&settings).to_toml();
+        let mut parsed = Settings::default();
+        ConfigFile::from_toml(&printed)
+            .unwrap()
+            .apply(&mut parsed, "any")
+            .unwrap();
+
+        assert_eq!(parsed, settings);
+    }
+
+    #[test]
+    fn test_invalid_config_files() {
+        assert!(ConfigFile::from_toml("sacle = 8").is_err());
+        assert!(ConfigFile::from_toml("scale = 0")
+            .unwrap()
+            .apply(&mut Settings::default(), "pong")
+            .is_err());
+        assert!(ConfigFile::from_toml("[quirks]\nwarp = true")
+            .unwrap()
+            .apply(&mut Settings::default(), "pong")
+            .is_err());
+        assert!(
+            ConfigFile::from_toml("[roms.pong.audio]\nwaveform = \"saw\"")
+                .unwrap()
+                .apply(&mut Settings::default(), "pong")
+                .is_err()
+        );
+    }
+}
@@ -1,11 +1,35 @@
+use crate::audio::Waveform;
+
 pub const FRAME_TIME_60HZ: f32 = 1.0 / 60.0;
-pub const FRAME_TIME_700HZ: f32 = 1.0 / 700.0;
+pub const INSTRUCTIONS_PER_SECOND: u32 = 700;
+pub const FAST_FORWARD_MULTIPLIER: u32 = 4;
+pub const MEMORY_SIZE: usize = 0x1000;
 pub const PROGRAM_START_ADDRESS: usize = 0x200;
+/// The CHIP-8X interpreter took up the first 768 bytes.
+pub const CHIP8X_PROGRAM_START_ADDRESS: usize = 0x300;
+/// Hi-res programs start by jumping over the interpreter patch that comes
+/// with them, to where the program proper starts.
+pub const HIRES_HEADER: [u8; 2] = [0x12, 0x60];
+pub const HIRES_START_ADDRESS: usize = 0x2C0;
 pub const FONT_START_ADRESS: usize = 0x50;
+pub const STACK_DEPTH: usize = 16;
+pub const VIP_STACK_DEPTH: usize = 12;
+
+// COSMAC VIP memory layout
+pub const VIP_FONT_ADDRESS: usize = 0x110;
+/// Start of the interpreter's stack and variables, past the end of programs.
+pub const VIP_WORK_AREA_ADDRESS: usize = 0xEA0;
+/// The VIP interpreter kept return addresses just below here, growing down.
+pub const VIP_STACK_ADDRESS: usize = 0xED0;
+/// Return addresses that fit between the work area and [`VIP_STACK_ADDRESS`].
+pub const VIP_MEMORY_STACK_DEPTH: usize = (VIP_STACK_ADDRESS - VIP_WORK_AREA_ADDRESS) / 2;
+pub const VIP_REGISTERS_ADDRESS: usize = 0xEF0;
+pub const VIP_DISPLAY_ADDRESS: usize = 0xF00;
 
 // Display
 pub const WIDTH: usize = 64;
 pub const HEIGHT: usize = 32;
+pub const HIRES_HEIGHT: usize = 64;
 pub const SCALE: usize = 10;
 
 // Quirks
@@ -14,3 +38,10 @@ pub const SHIFT_USE_VY: bool = false;
 pub const BNNN: bool = false;
 pub const STORE_LOAD_INCREMENTS_I: bool = true;
 pub const DISPLAY_WAIT: bool = true;
+pub const MEMORY_INCREMENT_BY_X: bool = false;
+pub const WRAP: bool = false;
+
+// Audio
+pub const BEEP_FREQUENCY: f32 = 440.0;
+pub const BEEP_VOLUME: f32 = 0.25;
+pub const BEEP_WAVEFORM: Waveform = Waveform::Sine;
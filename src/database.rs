@@ -0,0 +1,291 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+
+use crate::{
+    consts::{STACK_DEPTH, VIP_STACK_DEPTH},
+    font::FontPreset,
+    palette::{parse_color, Palette},
+    quirks::{DatabaseQuirks, Quirks},
+    variant::Variant,
+};
+
+const BUNDLED_PROGRAMS: &str = include_str!("../data/chip-8-database/programs.json");
+const BUNDLED_HASHES: &str = include_str!("../data/chip-8-database/sha1-hashes.json");
+const BUNDLED_PLATFORMS: &str = include_str!("../data/chip-8-database/platforms.json");
+
+/// Offline copy of the [chip-8-database](https://github.com/chip-8/chip-8-database),
+/// with programs looked up by the SHA-1 of the ROM file.
+pub struct Database {
+    programs: Vec<Program>,
+    hashes: HashMap<String, usize>,
+    platforms: Vec<Platform>,
+}
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, DatabaseQuirks>,
+    tickrate: Option<u32>,
+    colors: Option<Colors>,
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Platform {
+    pub id: String,
+    pub name: String,
+    pub default_tickrate: u32,
+    pub quirks: DatabaseQuirks,
+}
+
+impl Platform {
+    /// Subroutines the platform's interpreter can nest: the ones for the
+    /// COSMAC VIP had room for 12 return addresses, later ones for 16.
+    pub fn stack_depth(&self) -> usize {
+        match self.id.as_str() {
+            "originalChip8" | "hybridVIP" | "chip8x" => VIP_STACK_DEPTH,
+            _ => STACK_DEPTH,
+        }
+    }
+
+    /// The font the platform's interpreter came with.
+    pub fn font(&self) -> FontPreset {
+        match self.id.as_str() {
+            "originalChip8" | "hybridVIP" | "chip8x" => FontPreset::Vip,
+            "chip48" | "superchip1" | "superchip" => FontPreset::Schip,
+            _ => FontPreset::Octo,
+        }
+    }
+
+    pub fn variant(&self) -> Variant {
+        match self.id.as_str() {
+            "chip8x" => Variant::Chip8x,
+            _ => Variant::Chip8,
+        }
+    }
+}
+
+/// What the database knows about a ROM, already resolved against its platform.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub platform: Option<String>,
+    pub quirks: Quirks,
+    pub tickrate: Option<u32>,
+    pub stack_depth: Option<usize>,
+    pub font: Option<FontPreset>,
+    pub variant: Option<Variant>,
+    pub palette: Option<Palette>,
+    /// Which CHIP-8 key the game uses for `up`, `down`, `left`, `right`, `a`
+    /// and `b`.
+    pub keys: HashMap<String, u8>,
+}
+
+impl Database {
+    pub fn from_json(programs: &str, hashes: &str, platforms: &str) -> Result<Self, String> {
+        Ok(Database {
+            programs: serde_json::from_str(programs).map_err(|e| e.to_string())?,
+            hashes: serde_json::from_str(hashes).map_err(|e| e.to_string())?,
+            platforms: serde_json::from_str(platforms).map_err(|e| e.to_string())?,
+        })
+    }
+
+    pub fn bundled() -> Self {
+        Database::from_json(BUNDLED_PROGRAMS, BUNDLED_HASHES, BUNDLED_PLATFORMS)
+            .expect("bundled database is valid")
+    }
+
+    /// Reads `programs.json`, `sha1-hashes.json` and `platforms.json` from the
+    /// `database` folder of a chip-8-database checkout.
+    pub fn from_dir(dir: &Path) -> Result<Self, String> {
+        let read =
+            |name: &str| fs::read_to_string(dir.join(name)).map_err(|e| format!("{}: {e}", name));
+
+        Database::from_json(
+            &read("programs.json")?,
+            &read("sha1-hashes.json")?,
+            &read("platforms.json")?,
+        )
+    }
+
+    /// Uses a full copy of the database from the user's config directory when
+    /// there is one, and the bundled copy otherwise.
+    pub fn load() -> Self {
+        let Some(dir) = dirs::config_dir().map(|dir| dir.join("chip-8").join("database")) else {
+            return Database::bundled();
+        };
+        if !dir.exists() {
+            return Database::bundled();
+        }
+
+        Database::from_dir(&dir).unwrap_or_else(|e| {
+            eprintln!("failed to load database from {}: {e}", dir.display());
+            Database::bundled()
+        })
+    }
+
+    pub fn platform(&self, id: &str) -> Option<&Platform> {
+        self.platforms.iter().find(|platform| platform.id == id)
+    }
+
+    pub fn lookup(&self, program: &[u8]) -> Option<RomInfo> {
+        let hash = sha1_hex(program);
+        let entry = self.programs.get(*self.hashes.get(&hash)?)?;
+        let rom = entry.roms.get(&hash)?;
+
+        let platform_id = rom
+            .platforms
+            .first()
+            .or_else(|| rom.quirky_platforms.keys().next());
+        let platform = platform_id.and_then(|id| self.platform(id));
+
+        let mut quirks = Quirks::default();
+        if let Some(platform) = platform {
+            quirks.apply(&platform.quirks);
+        }
+        if let Some(overrides) = platform_id.and_then(|id| rom.quirky_platforms.get(id)) {
+            quirks.apply(overrides);
+        }
+
+        let colors: Vec<_> = rom
+            .colors
+            .iter()
+            .flat_map(|colors| colors.pixels.iter())
+            .filter_map(|color| parse_color(color))
+            .collect();
+
+        Some(RomInfo {
+            title: entry.title.clone(),
+            authors: entry.authors.clone(),
+            platform: platform.map(|platform| platform.name.clone()),
+            quirks,
+            tickrate: rom
+                .tickrate
+                .or(platform.map(|platform| platform.default_tickrate)),
+            stack_depth: platform.map(Platform::stack_depth),
+            font: platform.map(Platform::font),
+            variant: platform.map(Platform::variant),
+            palette: match colors[..] {
+                [background, foreground, ..] => Some(Palette::new(background, foreground)),
+                _ => None,
+            },
+            keys: rom.keys.clone(),
+        })
+    }
+}
+
+pub fn sha1_hex(bytes: &[u8]) -> String {
+    Sha1::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM: &[u8] = &[0x00, 0xE0, 0x12, 0x00];
+    const IBM_LOGO: &[u8] = &[
+        0x00, 0xE0, 0xA2, 0x2A, 0x60, 0x0C, 0x61, 0x08, 0xD0, 0x1F, 0x70, 0x09, 0xA2, 0x39, 0xD0,
+        0x1F, 0xA2, 0x48, 0x70, 0x08, 0xD0, 0x1F, 0x70, 0x04, 0xA2, 0x57, 0xD0, 0x1F, 0x70, 0x08,
+        0xA2, 0x66, 0xD0, 0x1F, 0x70, 0x08, 0xA2, 0x75, 0xD0, 0x1F, 0x12, 0x28, 0xFF, 0x00, 0xFF,
+        0x00, 0x3C, 0x00, 0x3C, 0x00, 0x3C, 0x00, 0x3C, 0x00, 0xFF, 0x00, 0xFF, 0xFF, 0x00, 0xFF,
+        0x00, 0x38, 0x00, 0x3F, 0x00, 0x3F, 0x00, 0x38, 0x00, 0xFF, 0x00, 0xFF, 0x80, 0x00, 0xE0,
+        0x00, 0xE0, 0x00, 0x80, 0x00, 0x80, 0x00, 0xE0, 0x00, 0xE0, 0x00, 0x80, 0xF8, 0x00, 0xFC,
+        0x00, 0x3E, 0x00, 0x3F, 0x00, 0x3B, 0x00, 0x39, 0x00, 0xF8, 0x00, 0xF8, 0x03, 0x00, 0x07,
+        0x00, 0x0F, 0x00, 0xBF, 0x00, 0xFB, 0x00, 0xF3, 0x00, 0xE3, 0x00, 0x43, 0xE0, 0x00, 0xE0,
+        0x00, 0x80, 0x00, 0x80, 0x00, 0x80, 0x00, 0x80, 0x00, 0xE0, 0x00, 0xE0,
+    ];
+
+    fn database() -> Database {
+        let programs = r##"[
+            {
+                "title": "Test Program",
+                "authors": ["Someone"],
+                "roms": {
+                    "ROM_HASH": {
+                        "platforms": ["superchip"],
+                        "quirkyPlatforms": { "superchip": { "wrap": true } },
+                        "colors": { "pixels": ["#000000", "#ffcc00"] },
+                        "keys": { "up": 5, "a": 6 }
+                    }
+                }
+            }
+        ]"##
+        .replace("ROM_HASH", &sha1_hex(ROM));
+        let hashes = format!(r#"{{ "{}": 0 }}"#, sha1_hex(ROM));
+
+        Database::from_json(&programs, &hashes, BUNDLED_PLATFORMS).unwrap()
+    }
+
+    #[test]
+    fn test_sha1() {
+        assert_eq!(sha1_hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    }
+
+    #[test]
+    fn test_bundled_database_loads() {
+        let database = Database::bundled();
+
+        assert!(database.platform("originalChip8").is_some());
+        assert!(database.lookup(ROM).is_none());
+
+        let info = database.lookup(IBM_LOGO).unwrap();
+        assert_eq!(info.title, "IBM Logo");
+        assert_eq!(info.platform.as_deref(), Some("Cosmac VIP CHIP-8"));
+        assert_eq!(info.tickrate, Some(15));
+        assert_eq!(info.stack_depth, Some(12));
+        assert_eq!(info.font, Some(FontPreset::Vip));
+        assert!(info.quirks.shift_use_vy);
+        assert!(info.quirks.store_load_increments_i);
+        assert!(info.quirks.display_wait);
+        assert!(info.quirks.vf_reset);
+    }
+
+    #[test]
+    fn test_lookup_resolves_platform() {
+        let info = database().lookup(ROM).unwrap();
+
+        assert_eq!(info.title, "Test Program");
+        assert_eq!(info.platform.as_deref(), Some("SUPER-CHIP 1.1"));
+        assert_eq!(info.tickrate, Some(30));
+        assert_eq!(info.stack_depth, Some(16));
+        assert_eq!(info.font, Some(FontPreset::Schip));
+        assert_eq!(info.variant, Some(Variant::Chip8));
+        assert_eq!(
+            info.palette,
+            Some(Palette::new([0x00, 0x00, 0x00], [0xff, 0xcc, 0x00]))
+        );
+        assert_eq!(info.keys.get("up"), Some(&5));
+
+        // From the platform
+        assert!(!info.quirks.shift_use_vy);
+        assert!(!info.quirks.store_load_increments_i);
+        // From the ROM's quirky platform entry
+        assert!(info.quirks.wrap);
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead, Write},
+};
+
+use crate::{consts::WIDTH, disasm::disassemble, headless::Headless};
+
+/// Gives up on `continue` after this many instructions without hitting a
+/// breakpoint, so a program that never stops does not hang the prompt.
+const CONTINUE_LIMIT: u32 = 10_000_000;
+
+const HELP: &str = "\
+step [n]          run n instructions (default 1)
+frame [n]         run n frames (default 1)
+continue          run until a breakpoint or a jump to itself
+break [addr]      toggle a breakpoint, or list them
+regs              show the registers, timers and stack
+mem <addr> [len]  dump memory (default 64 bytes)
+disasm [addr] [n] disassemble n instructions (default 10 from PC)
+display           print the display
+press <key>       hold down a CHIP-8 key
+release <key>     let go of a CHIP-8 key
+quit              leave the debugger";
+
+/// Steps through a program from commands typed at a prompt. Everything runs on
+/// [`Headless`], so timers only advance with frames.
+pub struct Debugger {
+    headless: Headless,
+    breakpoints: BTreeSet<u16>,
+}
+
+/// Reads commands from stdin until `quit` or the end of input.
+pub fn run(headless: Headless) -> Result<(), String> {
+    let mut debugger = Debugger::new(headless);
+    println!("{}", debugger.location());
+
+    let stdin = io::stdin();
+    loop {
+        print!("(chip-8) ");
+        io::stdout().flush().map_err(|e| e.to_string())?;
+
+        let mut line = String::new();
+        if stdin
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| e.to_string())?
+            == 0
+        {
+            return Ok(());
+        }
+
+        match debugger.execute(&line) {
+            Ok(Some(output)) => println!("{output}"),
+            Ok(None) => return Ok(()),
+            Err(e) => println!("error: {e}"),
+        }
+    }
+}
+
+impl Debugger {
+    pub fn new(headless: Headless) -> Self {
+        Debugger {
+            headless,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    /// Runs one command line. Returns what to print, or `None` to quit.
+    pub fn execute(&mut self, line: &str) -> Result<Option<String>, String> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(Some(String::new()));
+        };
+        let args: Vec<&str> = words.collect();
+
+        let output = match command {
+            "s" | "step" => {
+                for _ in 0..count(args.first(), 1)? {
+                    self.headless.step().map_err(|e| e.to_string())?;
+                }
+                self.location()
+            }
+            "f" | "frame" => {
+                for _ in 0..count(args.first(), 1)? {
+                    self.headless.run_frame().map_err(|e| e.to_string())?;
+                }
+                format!("frame {}\n{}", self.headless.frames(), self.location())
+            }
+            "c" | "continue" => self.resume()?,
+            "b" | "break" => match args.first() {
+                Some(address) => {
+                    let address = parse_address(address)?;
+                    if self.breakpoints.remove(&address) {
+                        format!("removed breakpoint at {address:#05X}")
+                    } else {
+                        self.breakpoints.insert(address);
+                        format!("breakpoint at {address:#05X}")
+                    }
+                }
+                None => self
+                    .breakpoints
+                    .iter()
+                    .map(|address| format!("{address:#05X}"))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            },
+            "r" | "regs" => self.registers(),
+            "m" | "mem" => {
+                let address = parse_address(args.first().ok_or("mem needs an address")?)? as usize;
+                let len = count(args.get(1), 64)? as usize;
+                self.memory(address, len)
+            }
+            "d" | "disasm" => {
+                let address = match args.first() {
+                    Some(address) => parse_address(address)?,
+                    None => self.headless.chip8.program_counter(),
+                };
+                let lines = count(args.get(1), 10)?;
+                (0..lines as u16)
+                    .map(|i| address.wrapping_add(i * 2) & 0xFFF)
+                    .map(|address| self.instruction_line(address))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            "display" => self.display(),
+            "press" | "release" => {
+                let key = args
+                    .first()
+                    .and_then(|key| u8::from_str_radix(key, 16).ok())
+                    .filter(|&key| key <= 0xF)
+                    .ok_or("expected a CHIP-8 key from 0 to F")?;
+                let chip8 = &mut self.headless.chip8;
+                if command == "press" {
+                    chip8.press_key(key);
+                } else {
+                    chip8.release_key(key);
+                }
+                String::new()
+            }
+            "h" | "help" => HELP.to_string(),
+            "q" | "quit" => return Ok(None),
+            _ => return Err(format!("unknown command '{command}', try help")),
+        };
+
+        Ok(Some(output))
+    }
+
+    /// Runs until the program counter lands on a breakpoint or the program
+    /// ends in a jump to itself.
+    fn resume(&mut self) -> Result<String, String> {
+        for _ in 0..CONTINUE_LIMIT {
+            self.headless.step().map_err(|e| e.to_string())?;
+
+            let pc = self.headless.chip8.program_counter();
+            if self.breakpoints.contains(&pc) {
+                return Ok(format!("breakpoint\n{}", self.location()));
+            }
+            if self.instruction(pc) == 0x1000 | pc {
+                return Ok(format!("stuck in a loop\n{}", self.location()));
+            }
+        }
+
+        Ok(format!(
+            "no breakpoint after {CONTINUE_LIMIT} instructions\n{}",
+            self.location()
+        ))
+    }
+
+    fn instruction(&self, address: u16) -> u16 {
+        let memory = self.headless.chip8.memory();
+        let address = address as usize;
+        u16::from_be_bytes([
+            memory[address % memory.len()],
+            memory[(address + 1) % memory.len()],
+        ])
+    }
+
+    fn instruction_line(&self, address: u16) -> String {
+        let instruction = self.instruction(address);
+        let marker = if self.breakpoints.contains(&address) {
+            '*'
+        } else {
+            ' '
+        };
+
+        format!(
+            "{marker}{address:#05X}: {instruction:04X}  {}",
+            disassemble(instruction)
+        )
+    }
+
+    fn location(&self) -> String {
+        self.instruction_line(self.headless.chip8.program_counter())
+    }
+
+    fn registers(&self) -> String {
+        let chip8 = &self.headless.chip8;
+        let registers: Vec<String> = chip8
+            .registers()
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("V{i:X}={value:02X}"))
+            .collect();
+        let stack: Vec<String> = chip8
+            .stack()
+            .iter()
+            .map(|address| format!("{address:#05X}"))
+            .collect();
+
+        format!(
+            "PC={:#05X} I={:#05X} DT={:02X} ST={:02X}\n{}\n{}\nstack: [{}]",
+            chip8.program_counter(),
+            chip8.index_register(),
+            chip8.delay_timer,
+            chip8.sound_timer,
+            registers[..8].join(" "),
+            registers[8..].join(" "),
+            stack.join(", ")
+        )
+    }
+
+    fn memory(&self, address: usize, len: usize) -> String {
+        let memory = self.headless.chip8.memory();
+        let end = (address + len).min(memory.len());
+
+        memory[address.min(end)..end]
+            .chunks(16)
+            .enumerate()
+            .map(|(i, bytes)| {
+                let bytes: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+                format!("{:#05X}: {}", address + i * 16, bytes.join(" "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn display(&self) -> String {
+        let display = &self.headless.chip8.display;
+
+        (0..display.len())
+            .map(|y| {
+                (0..WIDTH)
+                    .map(|x| if display[y][x] { '#' } else { '.' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn count(arg: Option<&&str>, default: u32) -> Result<u32, String> {
+    match arg {
+        Some(arg) => arg.parse().map_err(|_| format!("'{arg}' is not a number")),
+        None => Ok(default),
+    }
+}
+
+/// Addresses are hex, with or without a `0x` prefix.
+fn parse_address(address: &str) -> Result<u16, String> {
+    let digits = address.trim_start_matches("0x").trim_start_matches("0X");
+
+    u16::from_str_radix(digits, 16)
+        .ok()
+        .filter(|&address| address <= 0xFFF)
+        .ok_or_else(|| format!("'{address}' is not an address"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // V0 = 1; V0 += 1; loop forever
+    const PROGRAM: [u8; 6] = [0x60, 0x01, 0x70, 0x01, 0x12, 0x04];
+
+    fn debugger() -> Debugger {
+        Debugger::new(Headless::new(&PROGRAM, 10).unwrap())
+    }
+
+    #[test]
+    fn test_step_and_registers() {
+        let mut debugger = debugger();
+
+        assert_eq!(
+            debugger.execute("step 2").unwrap().unwrap(),
+            " 0x204: 1204  JP 0x204"
+        );
+        assert!(debugger.execute("regs").unwrap().unwrap().contains("V0=02"));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut debugger = debugger();
+
+        debugger.execute("break 202").unwrap();
+        assert_eq!(
+            debugger.execute("continue").unwrap().unwrap(),
+            "breakpoint\n*0x202: 7001  ADD V0, 0x01"
+        );
+        assert!(debugger
+            .execute("c")
+            .unwrap()
+            .unwrap()
+            .starts_with("stuck in a loop"));
+    }
+
+    #[test]
+    fn test_memory_and_errors() {
+        let mut debugger = debugger();
+
+        assert_eq!(
+            debugger.execute("mem 0x200 6").unwrap().unwrap(),
+            "0x200: 60 01 70 01 12 04"
+        );
+        assert!(debugger.execute("mem").is_err());
+        assert!(debugger.execute("break 0x1000").is_err());
+        assert!(debugger.execute("fly").is_err());
+        assert_eq!(debugger.execute("quit").unwrap(), None);
+    }
+}
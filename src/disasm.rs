@@ -0,0 +1,78 @@
+/// Turns an instruction into Cowgod-style assembly, e.g. `DRW V0, V1, 5`.
+/// Words that are not instructions come out as `DW` so the output can be fed
+/// back into the assembler.
+pub fn disassemble(instruction: u16) -> String {
+    let x = (instruction >> 8) & 0xF;
+    let y = (instruction >> 4) & 0xF;
+    let n = instruction & 0xF;
+    let nn = instruction & 0xFF;
+    let nnn = instruction & 0xFFF;
+
+    match ((instruction >> 12) & 0xF, n) {
+        (0x0, _) => match instruction {
+            0x00E0 => "CLS".to_string(),
+            0x00EE => "RET".to_string(),
+            _ => format!("SYS {nnn:#05X}"),
+        },
+        (0x1, _) => format!("JP {nnn:#05X}"),
+        (0x2, _) => format!("CALL {nnn:#05X}"),
+        (0x3, _) => format!("SE V{x:X}, {nn:#04X}"),
+        (0x4, _) => format!("SNE V{x:X}, {nn:#04X}"),
+        (0x5, 0x0) => format!("SE V{x:X}, V{y:X}"),
+        (0x6, _) => format!("LD V{x:X}, {nn:#04X}"),
+        (0x7, _) => format!("ADD V{x:X}, {nn:#04X}"),
+        (0x8, 0x0) => format!("LD V{x:X}, V{y:X}"),
+        (0x8, 0x1) => format!("OR V{x:X}, V{y:X}"),
+        (0x8, 0x2) => format!("AND V{x:X}, V{y:X}"),
+        (0x8, 0x3) => format!("XOR V{x:X}, V{y:X}"),
+        (0x8, 0x4) => format!("ADD V{x:X}, V{y:X}"),
+        (0x8, 0x5) => format!("SUB V{x:X}, V{y:X}"),
+        (0x8, 0x6) => format!("SHR V{x:X}, V{y:X}"),
+        (0x8, 0x7) => format!("SUBN V{x:X}, V{y:X}"),
+        (0x8, 0xE) => format!("SHL V{x:X}, V{y:X}"),
+        (0x9, 0x0) => format!("SNE V{x:X}, V{y:X}"),
+        (0xA, _) => format!("LD I, {nnn:#05X}"),
+        (0xB, _) => format!("JP V0, {nnn:#05X}"),
+        (0xC, _) => format!("RND V{x:X}, {nn:#04X}"),
+        (0xD, _) => format!("DRW V{x:X}, V{y:X}, {n}"),
+        (0xE, _) if nn == 0x9E => format!("SKP V{x:X}"),
+        (0xE, _) if nn == 0xA1 => format!("SKNP V{x:X}"),
+        (0xF, _) => match nn {
+            0x07 => format!("LD V{x:X}, DT"),
+            0x0A => format!("LD V{x:X}, K"),
+            0x15 => format!("LD DT, V{x:X}"),
+            0x18 => format!("LD ST, V{x:X}"),
+            0x1E => format!("ADD I, V{x:X}"),
+            0x29 => format!("LD F, V{x:X}"),
+            0x30 => format!("LD HF, V{x:X}"),
+            0x33 => format!("LD B, V{x:X}"),
+            0x55 => format!("LD [I], V{x:X}"),
+            0x65 => format!("LD V{x:X}, [I]"),
+            _ => format!("DW {instruction:#06X}"),
+        },
+        _ => format!("DW {instruction:#06X}"),
+    }
+}
+
+/// Disassembles a whole program as if every word were an instruction, one line
+/// per word with its address and raw value.
+pub fn disassemble_program(program: &[u8], start_address: usize) -> Vec<String> {
+    program
+        .chunks(2)
+        .enumerate()
+        .map(|(i, bytes)| {
+            let address = start_address + i * 2;
+            match bytes {
+                [high, low] => {
+                    let instruction = u16::from_be_bytes([*high, *low]);
+                    format!(
+                        "{address:#05X}: {instruction:04X}  {}",
+                        disassemble(instruction)
+                    )
+                }
+                [byte] => format!("{address:#05X}: {byte:02X}    DB {byte:#04X}"),
+                _ => unreachable!(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,697 @@
+use crate::{
+    audio::{Beeper, SAMPLE_RATE},
+    chip8::{Chip8, LoadError},
+    chip8x::tone_frequency,
+    consts::{FRAME_TIME_60HZ, WIDTH},
+    filters::Filters,
+    frame::Frame,
+    keymap::second_keypad_key,
+    menu::{Menu, MenuAction},
+    osd::Osd,
+    recorder::Recorder,
+    roms::RomEntry,
+    screenshot,
+    settings::{FastForward, Settings},
+    timing::FrameClock,
+    variant::Variant,
+};
+use sdl2::{
+    audio::{AudioDevice, AudioSpecDesired},
+    controller::GameController,
+    event::Event,
+    keyboard::{Keycode, Mod},
+    pixels::PixelFormatEnum,
+    render::{Texture, TextureCreator, WindowCanvas},
+    video::WindowContext,
+    EventPump, GameControllerSubsystem,
+};
+use std::{
+    collections::HashMap,
+    io,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// Builds the settings for a ROM picked from the menu, from its program and
+/// name.
+pub type SettingsFor = Box<dyn Fn(&[u8], &str) -> Result<Settings, String>>;
+
+pub struct Emulator {
+    canvas: WindowCanvas,
+    texture_creator: TextureCreator<WindowContext>,
+    /// Streamed to every frame, and only made again when the frame size
+    /// changes.
+    texture: Option<Texture>,
+    audio_device: AudioDevice<Beeper>,
+    event_pump: EventPump,
+    controller_subsystem: GameControllerSubsystem,
+    controllers: HashMap<u32, GameController>,
+    chip8: Chip8,
+    clock: FrameClock,
+    /// The running program, kept to reset the game.
+    program: Vec<u8>,
+    rom_name: String,
+    settings: Settings,
+    filters: Filters,
+    beeping: bool,
+    recorder: Option<Recorder>,
+    paused: bool,
+    fast_forwarding: bool,
+    osd: Osd,
+    menu: Option<Menu>,
+    settings_for: Option<SettingsFor>,
+}
+
+impl Emulator {
+    pub fn new(program: &[u8], rom_name: &str, settings: Settings) -> Result<Self, LoadError> {
+        // Load first, so a bad ROM is reported before a window opens.
+        let chip8 = settings.load(program)?;
+
+        let sdl_context = sdl2::init().expect("failed to initialize sdl!");
+
+        let video_subsystem = sdl_context
+            .video()
+            .expect("failed to initialize video subsystem!");
+        let window = video_subsystem
+            .window(
+                "Chip-8",
+                (WIDTH * settings.scale) as u32,
+                (chip8.display.len() * settings.scale) as u32,
+            )
+            .position_centered()
+            .build()
+            .expect("failed to make a window!");
+        let canvas = window
+            .into_canvas()
+            .build()
+            .expect("failed to make a canvas!");
+        let texture_creator = canvas.texture_creator();
+
+        let audio_settings = settings.audio;
+        let audio_subsystem = sdl_context
+            .audio()
+            .expect("failed to initialize audio subsystem!");
+        let desired_spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: Some(1024),
+        };
+        let audio_device = audio_subsystem
+            .open_playback(None, &desired_spec, |spec| {
+                Beeper::new(audio_settings, spec.freq)
+            })
+            .expect("failed to initialize audio device!");
+        // The beeper is gated on and off by the sound timer, so the device can
+        // keep playing silence the whole time.
+        audio_device.resume();
+
+        // Controllers that are already connected are reported through the same
+        // ControllerDeviceAdded events as hot-plugged ones.
+        let controller_subsystem = sdl_context
+            .game_controller()
+            .expect("failed to initialize game controller subsystem!");
+
+        let event_pump = sdl_context
+            .event_pump()
+            .expect("failed to obtain event pump!");
+
+        Ok(Self {
+            canvas,
+            texture_creator,
+            texture: None,
+            audio_device,
+            event_pump,
+            controller_subsystem,
+            controllers: HashMap::new(),
+            clock: settings.frame_clock(&chip8),
+            chip8,
+            program: program.to_vec(),
+            rom_name: rom_name.to_string(),
+            settings,
+            filters: Filters::default(),
+            beeping: false,
+            recorder: None,
+            paused: false,
+            fast_forwarding: false,
+            osd: Osd::default(),
+            menu: None,
+            settings_for: None,
+        })
+    }
+
+    /// ROMs loaded from the menu use `settings_for` instead of the settings of
+    /// the first ROM.
+    pub fn with_settings_for(mut self, settings_for: SettingsFor) -> Self {
+        self.settings_for = Some(settings_for);
+        self
+    }
+
+    pub fn run(&mut self, n_cycles: u32) {
+        let frame_time = Duration::from_secs_f32(FRAME_TIME_60HZ);
+        let mut next_frame = Instant::now();
+        let mut cycles = 0;
+        self.osd.message(self.rom_name.clone());
+
+        'running: while cycles < n_cycles {
+            // Handle events
+            let mut take_screenshot = false;
+            let mut toggle_recording = None;
+            let mut toggle_menu = false;
+            let mut menu_action = MenuAction::None;
+            let mut reset = false;
+            let mut advance = false;
+            for event in self.event_pump.poll_iter() {
+                let (mut pressed, mut released) = (None, None);
+
+                if let Some(menu) = &mut self.menu {
+                    match event {
+                        Event::Quit { .. } => break 'running,
+                        Event::KeyDown {
+                            keycode: Some(key), ..
+                        } => menu_action = menu.key(key),
+                        Event::ControllerButtonDown { button, .. } => {
+                            menu_action = menu.button(button)
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                match event {
+                    Event::Quit { .. }
+                    | Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } => {
+                        break 'running;
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F1),
+                        ..
+                    } => {
+                        self.filters.scanlines = !self.filters.scanlines;
+                        self.osd
+                            .message(on_off("SCANLINES", self.filters.scanlines));
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F2),
+                        ..
+                    } => {
+                        self.filters.pixel_grid = !self.filters.pixel_grid;
+                        self.osd
+                            .message(on_off("PIXEL GRID", self.filters.pixel_grid));
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F3),
+                        ..
+                    } => {
+                        self.filters.glow = !self.filters.glow;
+                        self.osd.message(on_off("GLOW", self.filters.glow));
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F4),
+                        ..
+                    } => {
+                        self.filters.barrel = !self.filters.barrel;
+                        self.osd
+                            .message(on_off("BARREL DISTORTION", self.filters.barrel));
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F5),
+                        ..
+                    } => reset = true,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F6),
+                        ..
+                    } => {
+                        self.paused = !self.paused;
+                        if !self.paused {
+                            self.osd.message("RESUMED");
+                        }
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F7),
+                        ..
+                    } => {
+                        self.paused = true;
+                        advance = true;
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Tab),
+                        keymod,
+                        ..
+                    } => {
+                        if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            self.settings.fast_forward = self.settings.fast_forward.next();
+                            self.osd.message(format!(
+                                "FAST-FORWARD SPEED {}",
+                                self.settings.fast_forward
+                            ));
+                        } else {
+                            self.fast_forwarding = !self.fast_forwarding;
+                            if !self.fast_forwarding {
+                                self.osd.message("NORMAL SPEED");
+                            }
+                        }
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F8),
+                        ..
+                    } => toggle_menu = true,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F11),
+                        ..
+                    } => self.osd.show_stats = !self.osd.show_stats,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F12),
+                        ..
+                    } => take_screenshot = true,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F9),
+                        ..
+                    } => toggle_recording = Some("gif"),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F10),
+                        ..
+                    } => toggle_recording = Some("y4m"),
+                    Event::KeyDown {
+                        keycode: Some(key), ..
+                    } => match second_keypad_key(key) {
+                        Some(key) if self.chip8.variant() == Variant::Chip8x => {
+                            self.chip8.second_keypad[key as usize] = true
+                        }
+                        _ => pressed = self.settings.keymap.get(key),
+                    },
+                    Event::KeyUp {
+                        keycode: Some(key), ..
+                    } => match second_keypad_key(key) {
+                        Some(key) if self.chip8.variant() == Variant::Chip8x => {
+                            self.chip8.second_keypad[key as usize] = false
+                        }
+                        _ => released = self.settings.keymap.get(key),
+                    },
+                    Event::ControllerButtonDown { button, .. } => {
+                        pressed = self.settings.keymap.get_button(button)
+                    }
+                    Event::ControllerButtonUp { button, .. } => {
+                        released = self.settings.keymap.get_button(button)
+                    }
+                    Event::ControllerDeviceAdded { which, .. } => {
+                        match self.controller_subsystem.open(which) {
+                            Ok(controller) => {
+                                self.controllers
+                                    .insert(controller.instance_id(), controller);
+                            }
+                            Err(e) => eprintln!("failed to open game controller: {e}"),
+                        }
+                    }
+                    Event::ControllerDeviceRemoved { which, .. } => {
+                        self.controllers.remove(&which);
+                    }
+                    _ => {}
+                }
+
+                if let Some(chip8_key) = pressed {
+                    self.chip8.press_key(chip8_key);
+                }
+
+                if let Some(chip8_key) = released {
+                    self.chip8.release_key(chip8_key);
+                }
+            }
+
+            if take_screenshot {
+                match self.screenshot() {
+                    Ok(()) => self.osd.message("SCREENSHOT SAVED"),
+                    Err(e) => {
+                        eprintln!("failed to save screenshot: {e}");
+                        self.osd.message("SCREENSHOT FAILED");
+                    }
+                }
+            }
+
+            if let Some(format) = toggle_recording {
+                match self.toggle_recording(format) {
+                    Ok(()) if self.recorder.is_some() => self
+                        .osd
+                        .message(format!("RECORDING {}", format.to_uppercase())),
+                    Ok(()) => self.osd.message("RECORDING SAVED"),
+                    Err(e) => {
+                        eprintln!("failed to toggle recording: {e}");
+                        self.osd.message("RECORDING FAILED");
+                    }
+                }
+            }
+
+            if reset {
+                match self.settings.load(&self.program) {
+                    Ok(chip8) => {
+                        self.clock = self.settings.frame_clock(&chip8);
+                        self.chip8 = chip8;
+                        self.osd.message("RESET");
+                    }
+                    Err(e) => eprintln!("failed to reset: {e}"),
+                }
+            }
+
+            if toggle_menu {
+                self.open_menu();
+            }
+
+            match menu_action {
+                MenuAction::None => {}
+                MenuAction::Close => self.menu = None,
+                MenuAction::Load(entry) => match self.load(&entry) {
+                    Ok(()) => {
+                        self.menu = None;
+                        self.osd.message(self.rom_name.clone());
+                    }
+                    Err(e) => {
+                        if let Some(menu) = &mut self.menu {
+                            menu.set_error(e);
+                        }
+                    }
+                },
+            }
+
+            // The game is paused while the menu is open.
+            if self.menu.is_some() {
+                self.render().unwrap();
+                ::std::thread::sleep(frame_time);
+                next_frame = Instant::now();
+                continue;
+            }
+
+            // Update
+            let frames = if self.paused {
+                u32::from(advance)
+            } else if self.fast_forwarding {
+                match self.settings.fast_forward {
+                    FastForward::Multiplier(multiplier) => multiplier,
+                    FastForward::Uncapped => u32::MAX,
+                }
+            } else {
+                1
+            };
+            // Uncapped fast-forward runs frames until the next one is due.
+            let deadline = next_frame + frame_time;
+            let mut frame_cycles = 0;
+            for _ in 0..frames {
+                frame_cycles += self.run_frame(n_cycles - cycles - frame_cycles);
+                if self.paused || cycles + frame_cycles >= n_cycles || Instant::now() >= deadline {
+                    break;
+                }
+            }
+            cycles += frame_cycles;
+            self.osd.tick(frame_cycles);
+
+            let muted = self.paused || (self.fast_forwarding && self.settings.mute_fast_forward);
+            let beeping = self.chip8.sound_timer > 0 && !muted;
+            if beeping != self.beeping {
+                self.beeping = beeping;
+                self.audio_device.lock().set_gate(self.beeping);
+            }
+            if let Some(value) = self.chip8.output_port {
+                self.audio_device
+                    .lock()
+                    .set_frequency(tone_frequency(value));
+            }
+
+            // Render
+            self.render().unwrap();
+
+            // Time management
+            next_frame += frame_time;
+            let now = Instant::now();
+            if next_frame > now {
+                ::std::thread::sleep(next_frame - now);
+            } else {
+                // Running behind, e.g. while fast-forwarding: don't try to
+                // catch up afterwards.
+                next_frame = now;
+            }
+        }
+
+        if let Some(recorder) = self.recorder.take() {
+            if let Err(e) = recorder.finish() {
+                eprintln!("failed to finish recording: {e}");
+            }
+        }
+    }
+
+    /// Runs instructions until the frame clock ends the frame, but no more
+    /// than `max_cycles`. Returns the number of instructions run. A fault
+    /// pauses on the instruction that caused it, which can then be reset.
+    fn run_frame(&mut self, max_cycles: u32) -> u32 {
+        let mut cycles = 0;
+        let mut frame_ended = false;
+        while cycles < max_cycles && !frame_ended {
+            if let Err(fault) = self.chip8.cycle() {
+                let address = self.chip8.program_counter();
+                self.osd.message(format!("{address:#05X}: {fault}"));
+                self.paused = true;
+                return cycles;
+            }
+            frame_ended = self.clock.tick(&self.chip8);
+            cycles += 1;
+        }
+
+        if frame_ended {
+            self.chip8.vblank();
+
+            if let Some(recorder) = &mut self.recorder {
+                if let Err(e) = recorder.capture(&self.chip8) {
+                    eprintln!("failed to record frame: {e}");
+                    self.recorder = None;
+                }
+            }
+        }
+
+        cycles
+    }
+
+    fn render(&mut self) -> Result<(), String> {
+        let mut frame = Frame::from_chip8(&self.chip8, &self.settings.palette, self.settings.scale);
+        self.filters.apply(&mut frame);
+
+        let status = if self.paused {
+            Some("PAUSED".to_string())
+        } else if self.fast_forwarding {
+            Some(format!(">> {}", self.settings.fast_forward))
+        } else {
+            None
+        };
+        self.osd.draw(&mut frame, &self.rom_name, status.as_deref());
+
+        if let Some(menu) = &mut self.menu {
+            menu.draw(&mut frame);
+        }
+
+        let size = (frame.width as u32, frame.height as u32);
+        let stale = self.texture.as_ref().is_none_or(|texture| {
+            let query = texture.query();
+            (query.width, query.height) != size
+        });
+        if stale {
+            // A ROM from the menu can have a display of another height.
+            if self.canvas.window().size() != size {
+                self.canvas
+                    .window_mut()
+                    .set_size(size.0, size.1)
+                    .map_err(|e| e.to_string())?;
+            }
+
+            let texture = self
+                .texture_creator
+                .create_texture_streaming(PixelFormatEnum::RGB24, size.0, size.1)
+                .map_err(|e| e.to_string())?;
+            if let Some(old) = self.texture.replace(texture) {
+                // SAFETY: the canvas that made it is still alive, and nothing
+                // else refers to it.
+                unsafe { old.destroy() };
+            }
+        }
+
+        let texture = self.texture.as_mut().expect("texture made above");
+        texture
+            .update(None, &frame.data, frame.pitch())
+            .map_err(|e| e.to_string())?;
+
+        self.canvas.copy(texture, None, None)?;
+        self.canvas.present();
+
+        Ok(())
+    }
+
+    fn open_menu(&mut self) {
+        let Some(dir) = &self.settings.rom_dir else {
+            eprintln!("no ROM folder configured, see --rom-dir");
+            self.osd.message("NO ROM FOLDER, SEE --ROM-DIR");
+            return;
+        };
+
+        let mut menu = Menu::open(dir);
+        menu.select(&self.rom_name);
+        self.menu = Some(menu);
+
+        // Keys held when the menu opened would otherwise stay pressed, and
+        // finish an FX0A with a release made in the menu.
+        self.chip8.release_all_keys();
+        self.beeping = false;
+        self.audio_device.lock().set_gate(false);
+    }
+
+    /// Replaces the running game with a fresh `Chip8` running `entry`.
+    fn load(&mut self, entry: &RomEntry) -> Result<(), String> {
+        let program = entry.read()?;
+        let rom_name = entry.rom_name();
+        let settings = match &self.settings_for {
+            Some(settings_for) => settings_for(&program, &rom_name)?,
+            None => self.settings.clone(),
+        };
+        let chip8 = settings
+            .load(&program)
+            .map_err(|e| format!("{}: {e}", entry.name))?;
+
+        if let Some(recorder) = self.recorder.take() {
+            if let Err(e) = recorder.finish() {
+                eprintln!("failed to finish recording: {e}");
+            }
+        }
+
+        let sample_rate = self.audio_device.spec().freq;
+        *self.audio_device.lock() = Beeper::new(settings.audio, sample_rate);
+        self.beeping = false;
+
+        self.clock = settings.frame_clock(&chip8);
+        self.chip8 = chip8;
+        self.program = program;
+        self.rom_name = rom_name;
+        self.settings = settings;
+        self.paused = false;
+
+        Ok(())
+    }
+
+    /// Saves the current display both upscaled with the active filters and at
+    /// its native resolution, in the working directory.
+    fn screenshot(&self) -> io::Result<()> {
+        let dir = Path::new(".");
+        screenshot::save_scaled(
+            &self.chip8,
+            &self.settings.palette,
+            &self.filters,
+            self.settings.scale,
+            &screenshot::timestamped_path(dir, &self.rom_name, "", "png"),
+        )?;
+        screenshot::save_native(
+            &self.chip8,
+            &self.settings.palette,
+            &screenshot::timestamped_path(dir, &self.rom_name, "-native", "png"),
+        )
+    }
+
+    /// Stops the running recording, or starts a new one in the working
+    /// directory. `y4m` recordings also write the beeper to a `.wav` file.
+    fn toggle_recording(&mut self, format: &str) -> io::Result<()> {
+        if let Some(recorder) = self.recorder.take() {
+            return recorder.finish();
+        }
+
+        let path = screenshot::timestamped_path(Path::new("."), &self.rom_name, "", format);
+        self.recorder = Some(match format {
+            "gif" => Recorder::gif(
+                &path,
+                &self.settings.palette,
+                self.settings.scale,
+                self.chip8.display.len(),
+            )?,
+            _ => Recorder::video(
+                &path,
+                &self.settings.palette,
+                &self.filters,
+                self.settings.scale,
+                self.chip8.display.len(),
+                &self.settings.audio,
+            )?,
+        });
+
+        Ok(())
+    }
+}
+
+fn on_off(name: &str, on: bool) -> String {
+    format!("{name} {}", if on { "ON" } else { "OFF" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_chip8_logo() {
+        let program = fs::read("tests/1-chip8-logo.ch8").expect("file not found!");
+
+        let mut emulator = Emulator::new(&program, "1-chip8-logo", Settings::default()).unwrap();
+        emulator.run(39);
+        emulator.run(u32::MAX);
+    }
+
+    #[test]
+    fn test_ibm_logo() {
+        let program = fs::read("tests/2-ibm-logo.ch8").expect("file not found!");
+
+        let mut emulator = Emulator::new(&program, "2-ibm-logo", Settings::default()).unwrap();
+        emulator.run(20);
+        emulator.run(u32::MAX);
+    }
+
+    #[test]
+    fn test_corax() {
+        let program = fs::read("tests/3-corax+.ch8").expect("file not found!");
+
+        let mut emulator = Emulator::new(&program, "3-corax+", Settings::default()).unwrap();
+        emulator.run(u32::MAX);
+    }
+
+    #[test]
+    fn test_flags() {
+        let program = fs::read("tests/4-flags.ch8").expect("file not found!");
+
+        let mut emulator = Emulator::new(&program, "4-flags", Settings::default()).unwrap();
+        emulator.run(u32::MAX);
+    }
+
+    #[test]
+    fn test_quirks() {
+        let program = fs::read("tests/5-quirks.ch8").expect("file not found!");
+
+        let mut emulator = Emulator::new(&program, "5-quirks", Settings::default()).unwrap();
+        emulator.run(u32::MAX);
+    }
+
+    #[test]
+    fn test_keypad() {
+        let program = fs::read("tests/6-keypad.ch8").expect("file not found!");
+
+        let mut emulator = Emulator::new(&program, "6-keypad", Settings::default()).unwrap();
+        emulator.run(u32::MAX);
+    }
+
+    #[test]
+    fn test_beep() {
+        let program = fs::read("tests/7-beep.ch8").expect("file not found!");
+
+        let mut emulator = Emulator::new(&program, "7-beep", Settings::default()).unwrap();
+        emulator.run(u32::MAX);
+    }
+
+    #[test]
+    fn test_scrolling() {
+        let program = fs::read("tests/8-scrolling.ch8").expect("file not found!");
+
+        let mut emulator = Emulator::new(&program, "8-scrolling", Settings::default()).unwrap();
+        emulator.run(u32::MAX);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::frame::Frame;
+
+const SCANLINE_INTENSITY: f32 = 0.6;
+const PIXEL_GRID_INTENSITY: f32 = 0.8;
+const GLOW_STRENGTH: f32 = 0.6;
+const BARREL_DISTORTION: f32 = 0.08;
+
+/// CPU post-processing applied to an upscaled frame before it is presented or
+/// exported. Every filter can be toggled independently at runtime.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Filters {
+    pub scanlines: bool,
+    pub pixel_grid: bool,
+    pub glow: bool,
+    pub barrel: bool,
+}
+
+impl Filters {
+    pub fn apply(&self, frame: &mut Frame) {
+        if self.glow {
+            glow(frame);
+        }
+        if self.scanlines {
+            scanlines(frame);
+        }
+        if self.pixel_grid {
+            pixel_grid(frame);
+        }
+        if self.barrel {
+            barrel(frame);
+        }
+    }
+}
+
+fn darken(pixel: &mut [u8], intensity: f32) {
+    for channel in pixel.iter_mut() {
+        *channel = (*channel as f32 * intensity) as u8;
+    }
+}
+
+/// Darkens the lower third of every scaled CHIP-8 row, or every other line if
+/// the frame is not upscaled.
+fn scanlines(frame: &mut Frame) {
+    let scale = frame.scale.max(1);
+    let dark_lines = if scale == 1 { 1 } else { (scale / 3).max(1) };
+    let pitch = frame.pitch();
+
+    for (y, row) in frame.data.chunks_exact_mut(pitch).enumerate() {
+        let line = if scale == 1 { y % 2 } else { y % scale };
+        let first_dark_line = if scale == 1 { 1 } else { scale - dark_lines };
+
+        if line >= first_dark_line {
+            darken(row, SCANLINE_INTENSITY);
+        }
+    }
+}
+
+/// Darkens the right and bottom edge of every scaled CHIP-8 pixel.
+fn pixel_grid(frame: &mut Frame) {
+    let scale = frame.scale;
+    if scale < 3 {
+        return;
+    }
+
+    let pitch = frame.pitch();
+    for (y, row) in frame.data.chunks_exact_mut(pitch).enumerate() {
+        for (x, pixel) in row.chunks_exact_mut(3).enumerate() {
+            if x % scale == scale - 1 || y % scale == scale - 1 {
+                darken(pixel, PIXEL_GRID_INTENSITY);
+            }
+        }
+    }
+}
+
+/// Spreads light from bright pixels into their darker neighbours using a box
+/// blur. Uniform areas are left untouched.
+fn glow(frame: &mut Frame) {
+    let radius = (frame.scale / 2).max(1);
+    let blurred = box_blur(frame, radius);
+
+    for (pixel, blurred) in frame.data.iter_mut().zip(blurred) {
+        if blurred > *pixel {
+            let boost = (blurred - *pixel) as f32 * GLOW_STRENGTH;
+            *pixel = (*pixel as f32 + boost).min(255.0) as u8;
+        }
+    }
+}
+
+fn box_blur(frame: &Frame, radius: usize) -> Vec<u8> {
+    let (width, height) = (frame.width, frame.height);
+    let mut horizontal = vec![0; frame.data.len()];
+    let mut blurred = vec![0; frame.data.len()];
+
+    for y in 0..height {
+        for x in 0..width {
+            let from = x.saturating_sub(radius);
+            let to = (x + radius).min(width - 1);
+            for channel in 0..3 {
+                let sum: u32 = (from..=to)
+                    .map(|i| frame.data[(y * width + i) * 3 + channel] as u32)
+                    .sum();
+                horizontal[(y * width + x) * 3 + channel] = (sum / (to - from + 1) as u32) as u8;
+            }
+        }
+    }
+
+    for y in 0..height {
+        let from = y.saturating_sub(radius);
+        let to = (y + radius).min(height - 1);
+        for x in 0..width {
+            for channel in 0..3 {
+                let sum: u32 = (from..=to)
+                    .map(|i| horizontal[(i * width + x) * 3 + channel] as u32)
+                    .sum();
+                blurred[(y * width + x) * 3 + channel] = (sum / (to - from + 1) as u32) as u8;
+            }
+        }
+    }
+
+    blurred
+}
+
+/// Bends the image outwards like the glass of a CRT. Areas that fall outside of
+/// the source frame are painted black.
+fn barrel(frame: &mut Frame) {
+    let source = frame.clone();
+    let (width, height) = (frame.width as f32, frame.height as f32);
+
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            let nx = 2.0 * (x as f32 + 0.5) / width - 1.0;
+            let ny = 2.0 * (y as f32 + 0.5) / height - 1.0;
+            let factor = 1.0 + BARREL_DISTORTION * (nx * nx + ny * ny);
+            let (sx, sy) = (nx * factor, ny * factor);
+
+            let color = if sx.abs() > 1.0 || sy.abs() > 1.0 {
+                [0, 0, 0]
+            } else {
+                let src_x = (((sx + 1.0) / 2.0) * width) as usize;
+                let src_y = (((sy + 1.0) / 2.0) * height) as usize;
+                source.pixel(src_x.min(source.width - 1), src_y.min(source.height - 1))
+            };
+
+            frame.set_pixel(x, y, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{consts::WIDTH, palette::Palette};
+
+    fn frame_with_pixel(scale: usize) -> Frame {
+        let mut display = [[false; WIDTH]; 32];
+        display[16][32] = true;
+        Frame::from_display(&display, &Palette::default(), scale)
+    }
+
+    #[test]
+    fn test_no_filters_leave_frame_untouched() {
+        let mut frame = frame_with_pixel(4);
+        let original = frame.data.clone();
+
+        Filters::default().apply(&mut frame);
+
+        assert_eq!(frame.data, original);
+    }
+
+    #[test]
+    fn test_scanlines_darken_bottom_of_each_row() {
+        let mut frame = frame_with_pixel(6);
+        let top = frame.pixel(0, 0);
+
+        Filters {
+            scanlines: true,
+            ..Default::default()
+        }
+        .apply(&mut frame);
+
+        assert_eq!(frame.pixel(0, 0), top);
+        assert!(frame.pixel(0, 5)[1] < top[1]);
+    }
+
+    #[test]
+    fn test_glow_only_brightens_around_lit_pixels() {
+        let mut frame = frame_with_pixel(4);
+        let far_away = frame.pixel(0, 0);
+        let neighbour = frame.pixel(32 * 4 - 1, 16 * 4);
+
+        Filters {
+            glow: true,
+            ..Default::default()
+        }
+        .apply(&mut frame);
+
+        assert_eq!(frame.pixel(0, 0), far_away);
+        assert!(frame.pixel(32 * 4 - 1, 16 * 4)[1] > neighbour[1]);
+    }
+
+    #[test]
+    fn test_barrel_keeps_center_and_blanks_corners() {
+        let mut frame = frame_with_pixel(4);
+        let center = frame.pixel(frame.width / 2, frame.height / 2);
+
+        Filters {
+            barrel: true,
+            ..Default::default()
+        }
+        .apply(&mut frame);
+
+        assert_eq!(frame.pixel(frame.width / 2, frame.height / 2), center);
+        assert_eq!(frame.pixel(0, 0), [0, 0, 0]);
+    }
+}
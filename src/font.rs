@@ -1,69 +1,321 @@
-use lazy_static::lazy_static;
+use std::{fmt, fs, str::FromStr};
 
-pub struct Character {
-    pub glyph: char,
-    pub bitmap: [u8; 5],
+/// Bytes of the 16 small digits, 4x5 pixels each.
+pub const SMALL_FONT_SIZE: usize = 16 * 5;
+/// Bytes of a big digit, 8x10 pixels.
+pub const BIG_DIGIT_SIZE: usize = 10;
+
+/// The built-in fonts, named after the interpreters they come from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FontPreset {
+    Octo,
+    Vip,
+    Dream6800,
+    Eti660,
+    Schip,
+}
+
+impl FromStr for FontPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "octo" => Ok(FontPreset::Octo),
+            "vip" => Ok(FontPreset::Vip),
+            "dream6800" => Ok(FontPreset::Dream6800),
+            "eti660" => Ok(FontPreset::Eti660),
+            "schip" => Ok(FontPreset::Schip),
+            _ => Err(format!(
+                "unknown font '{s}', expected octo, vip, dream6800, eti660 or schip"
+            )),
+        }
+    }
 }
 
-impl Character {
-    fn new(glyph: char, bitmap: [u8; 5]) -> Self {
-        Character { glyph, bitmap }
+impl fmt::Display for FontPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontPreset::Octo => write!(f, "octo"),
+            FontPreset::Vip => write!(f, "vip"),
+            FontPreset::Dream6800 => write!(f, "dream6800"),
+            FontPreset::Eti660 => write!(f, "eti660"),
+            FontPreset::Schip => write!(f, "schip"),
+        }
     }
 }
 
-lazy_static! {
-    pub static ref FONT_CHARACTERS: Vec<Character> = vec![
-        Character::new('0', [0xf0, 0x90, 0x90, 0x90, 0xf0]),
-        Character::new('1', [0x20, 0x60, 0x20, 0x20, 0x70]),
-        Character::new('2', [0xf0, 0x10, 0xf0, 0x80, 0xf0]),
-        Character::new('3', [0xf0, 0x10, 0xf0, 0x10, 0xf0]),
-        Character::new('4', [0x90, 0x90, 0xf0, 0x10, 0x10]),
-        Character::new('5', [0xf0, 0x80, 0xf0, 0x10, 0xf0]),
-        Character::new('6', [0xf0, 0x80, 0xf0, 0x90, 0xf0]),
-        Character::new('7', [0xf0, 0x10, 0x20, 0x40, 0x40]),
-        Character::new('8', [0xf0, 0x90, 0xf0, 0x90, 0xf0]),
-        Character::new('9', [0xf0, 0x90, 0xf0, 0x10, 0xf0]),
-        Character::new('A', [0xf0, 0x90, 0xf0, 0x90, 0x90]),
-        Character::new('B', [0xe0, 0x90, 0xe0, 0x90, 0xe0]),
-        Character::new('C', [0xf0, 0x80, 0x80, 0x80, 0xf0]),
-        Character::new('D', [0xe0, 0x90, 0x90, 0x90, 0xe0]),
-        Character::new('E', [0xf0, 0x80, 0xf0, 0x80, 0xf0]),
-        Character::new('F', [0xf0, 0x80, 0xf0, 0x80, 0x80]),
-    ];
+/// Digit sprites: 16 small 4x5 ones for `FX29` and, from SUPER-CHIP on, big
+/// 8x10 ones for `FX30`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Font {
+    /// The preset, or the file the font was loaded from.
+    pub name: String,
+    pub small: [u8; SMALL_FONT_SIZE],
+    /// Digits 0-9, or 0-F, one after the other. Empty when the font has none.
+    pub big: Vec<u8>,
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Font::preset(FontPreset::Octo)
+    }
+}
+
+impl Font {
+    pub fn preset(preset: FontPreset) -> Self {
+        let (small, big): (_, &[u8]) = match preset {
+            FontPreset::Octo => (SMALL, &OCTO_BIG),
+            FontPreset::Vip => (VIP_SMALL, &[]),
+            FontPreset::Dream6800 => (DREAM_6800_SMALL, &[]),
+            FontPreset::Eti660 => (ETI_660_SMALL, &[]),
+            FontPreset::Schip => (SMALL, &SCHIP_BIG),
+        };
+
+        Font {
+            name: preset.to_string(),
+            small,
+            big: big.to_vec(),
+        }
+    }
+
+    /// A preset by name, or else a font file.
+    pub fn open(name: &str) -> Result<Self, String> {
+        if let Ok(preset) = name.parse() {
+            return Ok(Font::preset(preset));
+        }
+
+        let bytes = fs::read(name).map_err(|e| format!("{name}: {e}"))?;
+        Font::from_bytes(name, &bytes).map_err(|e| format!("{name}: {e}"))
+    }
+
+    /// A font file is the 80 bytes of the small digits, optionally followed
+    /// by the 100 or 160 bytes of big ones.
+    pub fn from_bytes(name: &str, bytes: &[u8]) -> Result<Self, String> {
+        let big_sizes = [0, 10 * BIG_DIGIT_SIZE, 16 * BIG_DIGIT_SIZE];
+        if !big_sizes.contains(&bytes.len().wrapping_sub(SMALL_FONT_SIZE)) {
+            return Err(format!(
+                "a font is 80, 180 or 240 bytes, not {}",
+                bytes.len()
+            ));
+        }
+
+        let (small, big) = bytes.split_at(SMALL_FONT_SIZE);
+        Ok(Font {
+            name: name.to_string(),
+            small: small.try_into().unwrap(),
+            big: big.to_vec(),
+        })
+    }
+
+    /// Bytes the font takes in memory, the big digits right after the small
+    /// ones.
+    pub fn size(&self) -> usize {
+        SMALL_FONT_SIZE + self.big.len()
+    }
 }
 
+/// The small font of CHIP-48, SUPER-CHIP and Octo, which most interpreters
+/// since have used.
+#[rustfmt::skip]
+const SMALL: [u8; SMALL_FONT_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// The COSMAC VIP's, from its monitor ROM.
+#[rustfmt::skip]
+const VIP_SMALL: [u8; SMALL_FONT_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// The DREAM 6800's, 3 pixels wide.
+#[rustfmt::skip]
+const DREAM_6800_SMALL: [u8; SMALL_FONT_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+/// The ETI-660's, 3 pixels wide.
+#[rustfmt::skip]
+const ETI_660_SMALL: [u8; SMALL_FONT_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+/// SUPER-CHIP 1.1's, with only the decimal digits.
+#[rustfmt::skip]
+const SCHIP_BIG: [u8; 10 * BIG_DIGIT_SIZE] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+];
+
+/// Octo's, with all 16 digits.
+#[rustfmt::skip]
+const OCTO_BIG: [u8; 16 * BIG_DIGIT_SIZE] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn print_character(char: &Character) {
-        println!("Printing character {}", char.glyph);
-        let char_string: String = char
-            .bitmap
-            .iter()
-            .map(|line_byte| {
-                let mut line = String::with_capacity(4);
-
-                for shift in (4..=7).rev() {
-                    line.push(if line_byte & (1 << shift) != 0 {
-                        'X'
-                    } else {
-                        ' '
-                    });
-                }
-
-                line
-            })
-            .collect::<Vec<String>>()
-            .join("\n");
-
-        println!("{}\n", char_string)
+    #[test]
+    fn test_presets() {
+        // Digits 0 and F of the small font, and how many big digits there are
+        let presets: [(&str, [u8; 5], [u8; 5], usize); 5] = [
+            (
+                "octo",
+                [0xF0, 0x90, 0x90, 0x90, 0xF0],
+                [0xF0, 0x80, 0xF0, 0x80, 0x80],
+                16,
+            ),
+            (
+                "vip",
+                [0xF0, 0x90, 0x90, 0x90, 0xF0],
+                [0xF0, 0x80, 0xF0, 0x80, 0x80],
+                0,
+            ),
+            (
+                "dream6800",
+                [0xE0, 0xA0, 0xA0, 0xA0, 0xE0],
+                [0xE0, 0x80, 0xC0, 0x80, 0x80],
+                0,
+            ),
+            (
+                "eti660",
+                [0xE0, 0xA0, 0xA0, 0xA0, 0xE0],
+                [0xE0, 0x80, 0xC0, 0x80, 0x80],
+                0,
+            ),
+            (
+                "schip",
+                [0xF0, 0x90, 0x90, 0x90, 0xF0],
+                [0xF0, 0x80, 0xF0, 0x80, 0x80],
+                10,
+            ),
+        ];
+
+        for (preset, zero, f, big_digits) in presets {
+            let font = Font::open(preset).unwrap();
+            assert_eq!(font.name, preset);
+            assert_eq!(font.small[..5], zero, "{preset}");
+            assert_eq!(font.small[75..], f, "{preset}");
+            assert_eq!(font.big.len(), big_digits * BIG_DIGIT_SIZE, "{preset}");
+        }
+
+        // The small fonts differ in more than their width.
+        assert_eq!(
+            Font::preset(FontPreset::Vip).small[35..40],
+            [0xF0, 0x10, 0x10, 0x10, 0x10]
+        );
+        assert_eq!(
+            Font::preset(FontPreset::Octo).small[35..40],
+            [0xF0, 0x10, 0x20, 0x40, 0x40]
+        );
+
+        // Big digits are 10 bytes apart.
+        let schip = Font::preset(FontPreset::Schip);
+        assert_eq!(schip.big[..2], [0x3C, 0x7E]);
+        assert_eq!(schip.big[90..92], [0x3C, 0x7E]);
+        let octo = Font::preset(FontPreset::Octo);
+        assert_eq!(
+            octo.big[150..],
+            [0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0]
+        );
     }
 
     #[test]
-    fn test_characters() {
-        for character in FONT_CHARACTERS.iter() {
-            print_character(character);
-        }
+    fn test_font_file() {
+        let font = Font::from_bytes("font.bin", &[0xF0; 180]).unwrap();
+        assert_eq!(font.big.len(), 100);
+        assert_eq!(font.size(), 180);
+
+        assert!(Font::from_bytes("font.bin", &[0xF0; 80])
+            .unwrap()
+            .big
+            .is_empty());
+        assert!(Font::from_bytes("font.bin", &[0xF0; 100]).is_err());
+        assert!(Font::from_bytes("font.bin", &[0xF0; 79]).is_err());
+        assert!(Font::open("/no/such/font.bin").is_err());
     }
 }
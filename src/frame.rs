@@ -0,0 +1,80 @@
+use crate::{
+    chip8::Chip8,
+    chip8x::ColorBoard,
+    consts::WIDTH,
+    palette::{Palette, Rgb},
+};
+
+/// An RGB24 image of the CHIP-8 display, upscaled by an integer factor.
+///
+/// This is what gets presented by the SDL frontend, and it is plain memory so
+/// the post-processing filters and export paths can work on it without a GPU.
+#[derive(Clone)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub scale: usize,
+    pub data: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize, scale: usize) -> Self {
+        Frame {
+            width,
+            height,
+            scale,
+            data: vec![0; width * height * 3],
+        }
+    }
+
+    pub fn from_display(display: &[[bool; WIDTH]], palette: &Palette, scale: usize) -> Self {
+        Frame::render(display, scale, |_, _, pixel| palette.color(pixel))
+    }
+
+    /// The display in the colours of the CHIP-8X colour board.
+    pub fn from_colors(display: &[[bool; WIDTH]], colors: &ColorBoard, scale: usize) -> Self {
+        Frame::render(display, scale, |x, y, pixel| colors.color(x, y, pixel))
+    }
+
+    /// The display of `chip8`, in its colour board's colours if it has one.
+    pub fn from_chip8(chip8: &Chip8, palette: &Palette, scale: usize) -> Self {
+        match chip8.colors() {
+            Some(colors) => Frame::from_colors(&chip8.display, colors, scale),
+            None => Frame::from_display(&chip8.display, palette, scale),
+        }
+    }
+
+    fn render(
+        display: &[[bool; WIDTH]],
+        scale: usize,
+        color: impl Fn(usize, usize, bool) -> Rgb,
+    ) -> Self {
+        let mut frame = Frame::new(WIDTH * scale, display.len() * scale, scale);
+
+        for (y, row) in frame.data.chunks_exact_mut(frame.width * 3).enumerate() {
+            let display_row = &display[y / scale];
+
+            for (x, pixel) in row.chunks_exact_mut(3).enumerate() {
+                let (x, y) = (x / scale, y / scale);
+                pixel.copy_from_slice(&color(x, y, display_row[x]));
+            }
+        }
+
+        frame
+    }
+
+    /// Number of bytes in one row of pixels.
+    pub fn pitch(&self) -> usize {
+        self.width * 3
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        let i = (y * self.width + x) * 3;
+        [self.data[i], self.data[i + 1], self.data[i + 2]]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: [u8; 3]) {
+        let i = (y * self.width + x) * 3;
+        self.data[i..i + 3].copy_from_slice(&color);
+    }
+}
@@ -0,0 +1,231 @@
+use std::io;
+
+use crate::{
+    chip8::{Chip8, LoadError},
+    recorder::Recorder,
+    timing::FrameClock,
+};
+
+/// Runs a [`Chip8`] without a window, audio device or wall clock. Time only
+/// advances in whole 60Hz frames, so runs are reproducible and as fast as the
+/// host allows.
+pub struct Headless {
+    pub chip8: Chip8,
+    clock: FrameClock,
+    frames: u64,
+    recorder: Option<Recorder>,
+}
+
+impl Headless {
+    #[allow(unused)]
+    pub fn new(program: &[u8], cycles_per_frame: u32) -> Result<Self, LoadError> {
+        let mut chip8 = Chip8::new();
+        chip8.load_program(program)?;
+
+        Ok(Headless::with_chip8(
+            chip8,
+            FrameClock::fixed(cycles_per_frame),
+        ))
+    }
+
+    /// Runs an already set up `chip8`, e.g. one with quirks or a seed, with
+    /// frames ending when `clock` says so.
+    pub fn with_chip8(chip8: Chip8, clock: FrameClock) -> Self {
+        Headless {
+            chip8,
+            clock,
+            frames: 0,
+            recorder: None,
+        }
+    }
+
+    /// Number of 60Hz frames run so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Captures every following frame with `recorder`, finishing any recording
+    /// that was already running.
+    pub fn record(&mut self, recorder: Recorder) -> io::Result<()> {
+        self.stop_recording()?;
+        self.recorder = Some(recorder);
+
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> io::Result<()> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs a single instruction, ending the frame after the last instruction
+    /// of the frame. Returns whether a frame ended, and fails when the
+    /// instruction faults.
+    pub fn step(&mut self) -> io::Result<bool> {
+        self.chip8.cycle().map_err(io::Error::other)?;
+        if !self.clock.tick(&self.chip8) {
+            return Ok(false);
+        }
+
+        self.frames += 1;
+        self.chip8.vblank();
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.capture(&self.chip8)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Runs until the end of the current frame. When the number of
+    /// instructions left is known, all but the last run as translated blocks.
+    pub fn run_frame(&mut self) -> io::Result<()> {
+        if let Some(remaining) = self.clock.remaining() {
+            self.chip8.run(remaining - 1).map_err(io::Error::other)?;
+            self.clock.advance(remaining - 1);
+        }
+
+        while !self.step()? {}
+
+        Ok(())
+    }
+
+    pub fn run(&mut self, frames: u32) -> io::Result<()> {
+        for _ in 0..frames {
+            self.run_frame()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        audio::AudioSettings,
+        chip8x::{BACKGROUND_COLORS, FOREGROUND_COLORS},
+        consts::HEIGHT,
+        filters::Filters,
+        palette::Palette,
+        variant::Variant,
+    };
+    use std::{env, fs};
+
+    // I = font '0'; V0 = 5; ST = V0; draw '0' at (V0, V0); loop forever
+    const PROGRAM: [u8; 10] = [0xA0, 0x50, 0x60, 0x05, 0xF0, 0x18, 0xD0, 0x05, 0x12, 0x08];
+
+    #[test]
+    fn test_draw_waits_for_vblank() {
+        let mut headless = Headless::new(&PROGRAM, 12).unwrap();
+
+        headless.run(1).unwrap();
+        assert!(headless.chip8.display.iter().flatten().all(|&pixel| !pixel));
+
+        headless.run(1).unwrap();
+        assert!(headless.chip8.display[5][5]);
+    }
+
+    #[test]
+    fn test_record_gif() {
+        let path = env::temp_dir().join("chip8-test-recording.gif");
+        let mut headless = Headless::new(&PROGRAM, 12).unwrap();
+
+        headless
+            .record(Recorder::gif(&path, &Palette::default(), 2, HEIGHT).unwrap())
+            .unwrap();
+        headless.run(10).unwrap();
+        headless.stop_recording().unwrap();
+
+        let mut decoder = gif::DecodeOptions::new()
+            .read_info(fs::File::open(&path).unwrap())
+            .unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (128, 64));
+        let mut frames = 0;
+        while decoder.read_next_frame().unwrap().is_some() {
+            frames += 1;
+        }
+        assert_eq!(frames, 10);
+    }
+
+    #[test]
+    fn test_record_chip8x_colors() {
+        let path = env::temp_dir().join("chip8-test-chip8x.gif");
+        let mut chip8 = Chip8::new();
+        chip8.set_variant(Variant::Chip8x);
+        chip8.load_program(&PROGRAM).unwrap();
+        let mut headless = Headless::with_chip8(chip8, FrameClock::fixed(12));
+
+        headless
+            .record(Recorder::gif(&path, &Palette::default(), 1, HEIGHT).unwrap())
+            .unwrap();
+        headless.run(2).unwrap();
+        headless.stop_recording().unwrap();
+
+        let mut decoder = gif::DecodeOptions::new()
+            .read_info(fs::File::open(&path).unwrap())
+            .unwrap();
+        let palette = decoder.global_palette().unwrap().to_vec();
+        decoder.read_next_frame().unwrap();
+        let frame = decoder.read_next_frame().unwrap().unwrap();
+        let color = |x: usize, y: usize| {
+            let i = frame.buffer[y * 64 + x] as usize * 3;
+            [palette[i], palette[i + 1], palette[i + 2]]
+        };
+        assert_eq!(color(0, 0), BACKGROUND_COLORS[0]);
+        assert_eq!(color(5, 5), FOREGROUND_COLORS[1]);
+    }
+
+    #[test]
+    fn test_record_video_and_beeper() {
+        let path = env::temp_dir().join("chip8-test-recording.y4m");
+        let mut headless = Headless::new(&PROGRAM, 12).unwrap();
+
+        headless
+            .record(
+                Recorder::video(
+                    &path,
+                    &Palette::default(),
+                    &Filters::default(),
+                    1,
+                    HEIGHT,
+                    &AudioSettings::default(),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        headless.run(10).unwrap();
+        headless.stop_recording().unwrap();
+
+        let video = fs::read(&path).unwrap();
+        let header = b"YUV4MPEG2 W64 H32 F60:1 Ip A1:1 C444\n";
+        assert!(video.starts_with(header));
+        assert_eq!(video.len(), header.len() + 10 * (6 + 3 * 64 * 32));
+
+        let audio = fs::read(path.with_extension("wav")).unwrap();
+        assert_eq!(audio.len(), 44 + 10 * 735 * 2);
+        assert!(audio[44..].iter().any(|&byte| byte != 0));
+    }
+
+    #[test]
+    fn test_render_beeper_to_wav() {
+        let path = env::temp_dir().join("chip8-test-beeper.wav");
+        let mut headless = Headless::new(&PROGRAM, 12).unwrap();
+
+        headless
+            .record(Recorder::audio(&path, &AudioSettings::default()).unwrap())
+            .unwrap();
+        headless.run(10).unwrap();
+        headless.stop_recording().unwrap();
+
+        // The sound timer is set to 5 in the first frame and is ticked down at
+        // the end of every frame, so only the first four frames beep.
+        let audio = fs::read(&path).unwrap();
+        let frames: Vec<&[u8]> = audio[44..].chunks(735 * 2).collect();
+        assert_eq!(frames.len(), 10);
+        assert!(frames[..4].iter().all(|f| f.iter().any(|&b| b != 0)));
+        assert!(frames[5..].iter().all(|f| f.iter().all(|&b| b == 0)));
+    }
+}
@@ -0,0 +1,229 @@
+/// An instruction with its operands already extracted from the opcode, so it
+/// only has to be decoded once. Registers are given by their index.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    /// `00E0`
+    Clear,
+    /// `00EE`
+    Return,
+    /// `0NNN`, a machine language routine, which can't be run.
+    MachineRoutine(u16),
+    /// `1NNN`
+    Jump(u16),
+    /// `2NNN`
+    Call(u16),
+    /// `3XNN`
+    SkipIfEqual(u8, u8),
+    /// `4XNN`
+    SkipIfNotEqual(u8, u8),
+    /// `5XY0`
+    SkipIfRegistersEqual(u8, u8),
+    /// `6XNN`
+    Load(u8, u8),
+    /// `7XNN`
+    Add(u8, u8),
+    /// `8XY0`
+    Move(u8, u8),
+    /// `8XY1`
+    Or(u8, u8),
+    /// `8XY2`
+    And(u8, u8),
+    /// `8XY3`
+    Xor(u8, u8),
+    /// `8XY4`
+    AddRegisters(u8, u8),
+    /// `8XY5`
+    Subtract(u8, u8),
+    /// `8XY6`
+    ShiftRight(u8, u8),
+    /// `8XY7`
+    SubtractReversed(u8, u8),
+    /// `8XYE`
+    ShiftLeft(u8, u8),
+    /// `9XY0`
+    SkipIfRegistersNotEqual(u8, u8),
+    /// `ANNN`
+    LoadIndex(u16),
+    /// `BNNN`, or `BXNN` with the jump quirk.
+    JumpWithOffset(u16),
+    /// `CXNN`
+    Random(u8, u8),
+    /// `DXYN`
+    Draw(u8, u8, u8),
+    /// `EX9E`
+    SkipIfKey(u8),
+    /// `EXA1`
+    SkipIfNotKey(u8),
+    /// `FX07`
+    LoadDelayTimer(u8),
+    /// `FX0A`
+    WaitForKey(u8),
+    /// `FX15`
+    SetDelayTimer(u8),
+    /// `FX18`
+    SetSoundTimer(u8),
+    /// `FX1E`
+    AddToIndex(u8),
+    /// `FX29`
+    LoadFontCharacter(u8),
+    /// `FX30`
+    LoadBigFontCharacter(u8),
+    /// `FX33`
+    StoreBcd(u8),
+    /// `FX55`
+    StoreRegisters(u8),
+    /// `FX65`
+    LoadRegisters(u8),
+    Unknown(u16),
+    /// CHIP-8X `02A0`
+    CycleBackground,
+    /// CHIP-8X `5XY1`
+    AddNibbles(u8, u8),
+    /// CHIP-8X `BXY0`
+    SetZoneColor(u8, u8),
+    /// CHIP-8X `BXYN`
+    SetRowColor(u8, u8, u8),
+    /// CHIP-8X `EXF2`
+    SkipIfSecondKey(u8),
+    /// CHIP-8X `EXF5`
+    SkipIfNotSecondKey(u8),
+    /// CHIP-8X `FXF8`
+    Output(u8),
+    /// CHIP-8X `FXFB`
+    Input(u8),
+}
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Self {
+        // X and Y pick registers, N is a 4-bit number, NN an 8-bit immediate
+        // and NNN a 12-bit address.
+        let x = ((opcode >> 8) & 0xF) as u8;
+        let y = ((opcode >> 4) & 0xF) as u8;
+        let n = (opcode & 0xF) as u8;
+        let nn = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+
+        match opcode >> 12 {
+            0x0 => match opcode {
+                0x00E0 => Instruction::Clear,
+                0x00EE => Instruction::Return,
+                _ => Instruction::MachineRoutine(nnn),
+            },
+            0x1 => Instruction::Jump(nnn),
+            0x2 => Instruction::Call(nnn),
+            0x3 => Instruction::SkipIfEqual(x, nn),
+            0x4 => Instruction::SkipIfNotEqual(x, nn),
+            0x5 => Instruction::SkipIfRegistersEqual(x, y),
+            0x6 => Instruction::Load(x, nn),
+            0x7 => Instruction::Add(x, nn),
+            0x8 => match n {
+                0x0 => Instruction::Move(x, y),
+                0x1 => Instruction::Or(x, y),
+                0x2 => Instruction::And(x, y),
+                0x3 => Instruction::Xor(x, y),
+                0x4 => Instruction::AddRegisters(x, y),
+                0x5 => Instruction::Subtract(x, y),
+                0x6 => Instruction::ShiftRight(x, y),
+                0x7 => Instruction::SubtractReversed(x, y),
+                0xE => Instruction::ShiftLeft(x, y),
+                _ => Instruction::Unknown(opcode),
+            },
+            0x9 => Instruction::SkipIfRegistersNotEqual(x, y),
+            0xA => Instruction::LoadIndex(nnn),
+            0xB => Instruction::JumpWithOffset(nnn),
+            0xC => Instruction::Random(x, nn),
+            0xD => Instruction::Draw(x, y, n),
+            0xE => match nn {
+                0x9E => Instruction::SkipIfKey(x),
+                0xA1 => Instruction::SkipIfNotKey(x),
+                _ => Instruction::Unknown(opcode),
+            },
+            _ => match nn {
+                0x07 => Instruction::LoadDelayTimer(x),
+                0x0A => Instruction::WaitForKey(x),
+                0x15 => Instruction::SetDelayTimer(x),
+                0x18 => Instruction::SetSoundTimer(x),
+                0x1E => Instruction::AddToIndex(x),
+                0x29 => Instruction::LoadFontCharacter(x),
+                0x30 => Instruction::LoadBigFontCharacter(x),
+                0x33 => Instruction::StoreBcd(x),
+                0x55 => Instruction::StoreRegisters(x),
+                0x65 => Instruction::LoadRegisters(x),
+                _ => Instruction::Unknown(opcode),
+            },
+        }
+    }
+
+    /// Like [`Instruction::decode`], with the CHIP-8X additions. Its `BXYN`
+    /// replaces `BNNN`.
+    pub fn decode_chip8x(opcode: u16) -> Self {
+        let x = ((opcode >> 8) & 0xF) as u8;
+        let y = ((opcode >> 4) & 0xF) as u8;
+        let n = (opcode & 0xF) as u8;
+
+        match (opcode >> 12, opcode & 0xFF) {
+            _ if opcode == 0x02A0 => Instruction::CycleBackground,
+            (0x5, _) if n == 1 => Instruction::AddNibbles(x, y),
+            (0xB, _) if n == 0 => Instruction::SetZoneColor(x, y),
+            (0xB, _) => Instruction::SetRowColor(x, y, n),
+            (0xE, 0xF2) => Instruction::SkipIfSecondKey(x),
+            (0xE, 0xF5) => Instruction::SkipIfNotSecondKey(x),
+            (0xF, 0xF8) => Instruction::Output(x),
+            (0xF, 0xFB) => Instruction::Input(x),
+            _ => Instruction::decode(opcode),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(Instruction::decode(0x00E0), Instruction::Clear);
+        assert_eq!(
+            Instruction::decode(0x0123),
+            Instruction::MachineRoutine(0x123)
+        );
+        assert_eq!(Instruction::decode(0x7A05), Instruction::Add(0xA, 0x05));
+        assert_eq!(
+            Instruction::decode(0x8AB6),
+            Instruction::ShiftRight(0xA, 0xB)
+        );
+        assert_eq!(Instruction::decode(0xD125), Instruction::Draw(1, 2, 5));
+        assert_eq!(Instruction::decode(0xF355), Instruction::StoreRegisters(3));
+        assert_eq!(Instruction::decode(0x8AB9), Instruction::Unknown(0x8AB9));
+        assert_eq!(Instruction::decode(0xE1A2), Instruction::Unknown(0xE1A2));
+    }
+
+    #[test]
+    fn test_decode_chip8x() {
+        assert_eq!(
+            Instruction::decode_chip8x(0x02A0),
+            Instruction::CycleBackground
+        );
+        assert_eq!(
+            Instruction::decode_chip8x(0x5121),
+            Instruction::AddNibbles(1, 2)
+        );
+        assert_eq!(
+            Instruction::decode_chip8x(0x5120),
+            Instruction::SkipIfRegistersEqual(1, 2)
+        );
+        assert_eq!(
+            Instruction::decode_chip8x(0xB120),
+            Instruction::SetZoneColor(1, 2)
+        );
+        assert_eq!(
+            Instruction::decode_chip8x(0xB124),
+            Instruction::SetRowColor(1, 2, 4)
+        );
+        assert_eq!(
+            Instruction::decode_chip8x(0xE3F2),
+            Instruction::SkipIfSecondKey(3)
+        );
+        assert_eq!(Instruction::decode_chip8x(0xF3FB), Instruction::Input(3));
+        assert_eq!(Instruction::decode_chip8x(0x00E0), Instruction::Clear);
+    }
+}
@@ -0,0 +1,450 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::PathBuf,
+};
+
+use sdl2::{controller::Button, keyboard::Keycode};
+use serde::{Deserialize, Serialize};
+
+/// Maps physical keys and game controller buttons to CHIP-8 keys. A CHIP-8 key
+/// can be bound to any number of physical keys and buttons.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keymap {
+    bindings: HashMap<Keycode, u8>,
+    buttons: HashMap<Button, u8>,
+}
+
+/// Most games move with 2/4/6/8 and act with 5, so the d-pad and the face
+/// buttons go there.
+const CONTROLLER_LAYOUT: [(Button, u8); 10] = [
+    (Button::DPadUp, 0x2),
+    (Button::DPadLeft, 0x4),
+    (Button::DPadRight, 0x6),
+    (Button::DPadDown, 0x8),
+    (Button::A, 0x5),
+    (Button::B, 0xA),
+    (Button::X, 0xB),
+    (Button::Y, 0xC),
+    (Button::Back, 0x0),
+    (Button::Start, 0xF),
+];
+
+/// CHIP-8 keys in keypad order, matching the rows of the presets below.
+const KEYPAD_LAYOUT: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+];
+
+impl Keymap {
+    pub fn empty() -> Self {
+        Keymap {
+            bindings: HashMap::new(),
+            buttons: HashMap::new(),
+        }
+    }
+
+    /// Keymaps that put the CHIP-8 keypad on the same physical keys (the left
+    /// side of the keyboard) for a few keyboard layouts.
+    pub fn preset(name: &str) -> Option<Self> {
+        let keys = match name.to_ascii_lowercase().as_str() {
+            "qwerty" => [
+                Keycode::NUM_1,
+                Keycode::NUM_2,
+                Keycode::NUM_3,
+                Keycode::NUM_4,
+                Keycode::Q,
+                Keycode::W,
+                Keycode::E,
+                Keycode::R,
+                Keycode::A,
+                Keycode::S,
+                Keycode::D,
+                Keycode::F,
+                Keycode::Z,
+                Keycode::X,
+                Keycode::C,
+                Keycode::V,
+            ],
+            "azerty" => [
+                Keycode::AMPERSAND,
+                Keycode::from_i32('é' as i32)?,
+                Keycode::QUOTEDBL,
+                Keycode::QUOTE,
+                Keycode::A,
+                Keycode::Z,
+                Keycode::E,
+                Keycode::R,
+                Keycode::Q,
+                Keycode::S,
+                Keycode::D,
+                Keycode::F,
+                Keycode::W,
+                Keycode::X,
+                Keycode::C,
+                Keycode::V,
+            ],
+            "dvorak" => [
+                Keycode::NUM_1,
+                Keycode::NUM_2,
+                Keycode::NUM_3,
+                Keycode::NUM_4,
+                Keycode::QUOTE,
+                Keycode::COMMA,
+                Keycode::PERIOD,
+                Keycode::P,
+                Keycode::A,
+                Keycode::O,
+                Keycode::E,
+                Keycode::U,
+                Keycode::SEMICOLON,
+                Keycode::Q,
+                Keycode::J,
+                Keycode::K,
+            ],
+            _ => return None,
+        };
+
+        let mut keymap = Keymap::empty();
+        for (keycode, key) in keys.into_iter().zip(KEYPAD_LAYOUT) {
+            keymap.bind(keycode, key);
+        }
+        for (button, key) in CONTROLLER_LAYOUT {
+            keymap.bind_button(button, key);
+        }
+
+        // The number row of an AZERTY keyboard needs shift for digits, so also
+        // accept the digits themselves.
+        if name.eq_ignore_ascii_case("azerty") {
+            for (keycode, key) in [
+                Keycode::NUM_1,
+                Keycode::NUM_2,
+                Keycode::NUM_3,
+                Keycode::NUM_4,
+            ]
+            .into_iter()
+            .zip(KEYPAD_LAYOUT)
+            {
+                keymap.bind(keycode, key);
+            }
+        }
+
+        Some(keymap)
+    }
+
+    pub fn bind(&mut self, keycode: Keycode, key: u8) {
+        self.bindings.insert(keycode, key);
+    }
+
+    /// Removes every physical key bound to `key`.
+    pub fn unbind(&mut self, key: u8) {
+        self.bindings.retain(|_, &mut bound| bound != key);
+    }
+
+    pub fn get(&self, keycode: Keycode) -> Option<u8> {
+        self.bindings.get(&keycode).copied()
+    }
+
+    pub fn bind_button(&mut self, button: Button, key: u8) {
+        self.buttons.insert(button, key);
+    }
+
+    /// Removes every controller button bound to `key`.
+    pub fn unbind_buttons(&mut self, key: u8) {
+        self.buttons.retain(|_, &mut bound| bound != key);
+    }
+
+    pub fn get_button(&self, button: Button) -> Option<u8> {
+        self.buttons.get(&button).copied()
+    }
+
+    /// Binds the arrow keys, space, shift and the matching controller buttons
+    /// to the keys a game uses for `up`, `down`, `left`, `right`, `a` and `b`,
+    /// as listed by the ROM database.
+    pub fn apply_hints(&mut self, hints: &HashMap<String, u8>) {
+        let inputs = [
+            ("up", Keycode::UP, Button::DPadUp),
+            ("down", Keycode::DOWN, Button::DPadDown),
+            ("left", Keycode::LEFT, Button::DPadLeft),
+            ("right", Keycode::RIGHT, Button::DPadRight),
+            ("a", Keycode::SPACE, Button::A),
+            ("b", Keycode::LSHIFT, Button::B),
+        ];
+
+        for (hint, keycode, button) in inputs {
+            if let Some(&key) = hints.get(hint).filter(|&&key| key <= 0xF) {
+                self.bind(keycode, key);
+                self.bind_button(button, key);
+            }
+        }
+    }
+
+    /// Loads the keymap file from the user's config directory, applying the
+    /// section for `rom_name` if there is one. Falls back to QWERTY when there
+    /// is no file.
+    pub fn load(rom_name: &str) -> Result<Self, String> {
+        match keymap_path().map(fs::read_to_string) {
+            Some(Ok(contents)) => Keymap::from_toml(&contents, rom_name),
+            Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(Keymap::default()),
+        }
+    }
+
+    /// Lists every binding, so that applying the section to any keymap gives
+    /// back this one for every bound CHIP-8 key.
+    pub fn section(&self) -> KeymapSection {
+        let mut section = KeymapSection::default();
+
+        for (keycode, key) in &self.bindings {
+            section
+                .keys
+                .entry(format!("{key:X}"))
+                .or_default()
+                .push(keycode.name());
+        }
+        for (button, key) in &self.buttons {
+            section
+                .controller
+                .entry(format!("{key:X}"))
+                .or_default()
+                .push(button.string());
+        }
+        for names in section
+            .keys
+            .values_mut()
+            .chain(section.controller.values_mut())
+        {
+            names.sort();
+        }
+
+        section
+    }
+
+    pub fn from_toml(contents: &str, rom_name: &str) -> Result<Self, String> {
+        let file: KeymapFile = toml::from_str(contents).map_err(|e| e.to_string())?;
+
+        let mut keymap = Keymap::default();
+        file.global.apply(&mut keymap)?;
+        if let Some(overrides) = file.roms.get(rom_name) {
+            overrides.apply(&mut keymap)?;
+        }
+
+        Ok(keymap)
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::preset("qwerty").expect("qwerty preset exists")
+    }
+}
+
+/// The CHIP-8X second keypad, on the numeric keypad in the same order as the
+/// first one.
+const SECOND_KEYPAD: [Keycode; 16] = [
+    Keycode::KP_7,
+    Keycode::KP_8,
+    Keycode::KP_9,
+    Keycode::KP_DIVIDE,
+    Keycode::KP_4,
+    Keycode::KP_5,
+    Keycode::KP_6,
+    Keycode::KP_MULTIPLY,
+    Keycode::KP_1,
+    Keycode::KP_2,
+    Keycode::KP_3,
+    Keycode::KP_MINUS,
+    Keycode::KP_0,
+    Keycode::KP_PERIOD,
+    Keycode::KP_ENTER,
+    Keycode::KP_PLUS,
+];
+
+/// The CHIP-8X second keypad key on `keycode`, if any.
+pub fn second_keypad_key(keycode: Keycode) -> Option<u8> {
+    let position = SECOND_KEYPAD.iter().position(|&key| key == keycode)?;
+    Some(KEYPAD_LAYOUT[position])
+}
+
+/// `$CONFIG_DIR/chip-8/keymap.toml`, e.g. `~/.config/chip-8/keymap.toml`.
+pub fn keymap_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("chip-8").join("keymap.toml"))
+}
+
+/// ```toml
+/// preset = "azerty"
+///
+/// [keys]
+/// 5 = ["Up", "W"]
+///
+/// [controller]
+/// 5 = ["a", "rightshoulder"]
+///
+/// [roms.pong.keys]
+/// 1 = ["Left Shift"]
+///
+/// [roms.pong.controller]
+/// 1 = ["dpup"]
+/// 4 = ["dpdown"]
+/// ```
+///
+/// CHIP-8 keys are hex digits, physical keys use SDL key names and controller
+/// buttons use SDL game controller button names. Listing a CHIP-8 key replaces
+/// all of its bindings from the preset.
+#[derive(Deserialize, Default)]
+struct KeymapFile {
+    #[serde(flatten)]
+    global: KeymapSection,
+    #[serde(default)]
+    roms: HashMap<String, KeymapSection>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Default, PartialEq)]
+pub struct KeymapSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub controller: BTreeMap<String, Vec<String>>,
+}
+
+impl KeymapSection {
+    pub fn apply(&self, keymap: &mut Keymap) -> Result<(), String> {
+        if let Some(preset) = &self.preset {
+            *keymap = Keymap::preset(preset)
+                .ok_or_else(|| format!("unknown keymap preset '{preset}'"))?;
+        }
+
+        for (key, names) in &self.keys {
+            let key = parse_chip8_key(key)?;
+
+            keymap.unbind(key);
+            for name in names {
+                let keycode =
+                    Keycode::from_name(name).ok_or_else(|| format!("unknown key name '{name}'"))?;
+                keymap.bind(keycode, key);
+            }
+        }
+
+        for (key, names) in &self.controller {
+            let key = parse_chip8_key(key)?;
+
+            keymap.unbind_buttons(key);
+            for name in names {
+                let button = Button::from_string(name)
+                    .ok_or_else(|| format!("unknown controller button '{name}'"))?;
+                keymap.bind_button(button, key);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_chip8_key(key: &str) -> Result<u8, String> {
+    u8::from_str_radix(key, 16)
+        .ok()
+        .filter(|&key| key <= 0xF)
+        .ok_or_else(|| format!("'{key}' is not a CHIP-8 key"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_qwerty() {
+        let keymap = Keymap::default();
+
+        assert_eq!(keymap.get(Keycode::NUM_1), Some(0x1));
+        assert_eq!(keymap.get(Keycode::R), Some(0xD));
+        assert_eq!(keymap.get(Keycode::X), Some(0x0));
+        assert_eq!(keymap.get(Keycode::P), None);
+    }
+
+    #[test]
+    fn test_presets_cover_keypad() {
+        for name in ["qwerty", "azerty", "dvorak"] {
+            let keymap = Keymap::preset(name).unwrap();
+            let mut keys: Vec<u8> = keymap.bindings.values().copied().collect();
+            keys.sort();
+            keys.dedup();
+
+            assert_eq!(keys, (0..=0xF).collect::<Vec<u8>>(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_second_keypad() {
+        assert_eq!(second_keypad_key(Keycode::KP_7), Some(0x1));
+        assert_eq!(second_keypad_key(Keycode::KP_0), Some(0xA));
+        assert_eq!(second_keypad_key(Keycode::KP_PLUS), Some(0xF));
+        assert_eq!(second_keypad_key(Keycode::NUM_1), None);
+    }
+
+    #[test]
+    fn test_default_controller_layout() {
+        let keymap = Keymap::default();
+
+        assert_eq!(keymap.get_button(Button::DPadUp), Some(0x2));
+        assert_eq!(keymap.get_button(Button::DPadRight), Some(0x6));
+        assert_eq!(keymap.get_button(Button::A), Some(0x5));
+        assert_eq!(keymap.get_button(Button::Guide), None);
+    }
+
+    #[test]
+    fn test_database_hints() {
+        let mut keymap = Keymap::default();
+        keymap.apply_hints(&HashMap::from([
+            ("up".to_string(), 0x5),
+            ("a".to_string(), 0x6),
+        ]));
+
+        assert_eq!(keymap.get(Keycode::UP), Some(0x5));
+        assert_eq!(keymap.get_button(Button::DPadUp), Some(0x5));
+        assert_eq!(keymap.get(Keycode::SPACE), Some(0x6));
+        assert_eq!(keymap.get(Keycode::DOWN), None);
+    }
+
+    #[test]
+    fn test_multiple_keys_per_chip8_key() {
+        let keymap = Keymap::preset("azerty").unwrap();
+
+        assert_eq!(keymap.get(Keycode::AMPERSAND), Some(0x1));
+        assert_eq!(keymap.get(Keycode::NUM_1), Some(0x1));
+    }
+
+    #[test]
+    fn test_rom_section_overrides_global_preset() {
+        let contents = r#"
+            preset = "dvorak"
+
+            [roms.pong]
+            preset = "azerty"
+        "#;
+
+        assert_eq!(
+            Keymap::from_toml(contents, "tetris").unwrap(),
+            Keymap::preset("dvorak").unwrap()
+        );
+        assert_eq!(
+            Keymap::from_toml(contents, "pong").unwrap(),
+            Keymap::preset("azerty").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_section_round_trips() {
+        let keymap = Keymap::preset("dvorak").unwrap();
+        let mut applied = Keymap::empty();
+        keymap.section().apply(&mut applied).unwrap();
+
+        assert_eq!(applied, keymap);
+    }
+
+    #[test]
+    fn test_invalid_keymap_files() {
+        assert!(Keymap::from_toml("preset = \"colemak\"", "pong").is_err());
+        assert!(Keymap::from_toml("[keys]\n10 = []", "pong").is_err());
+    }
+}
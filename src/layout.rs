@@ -0,0 +1,55 @@
+use std::{fmt, str::FromStr};
+
+use crate::consts::{FONT_START_ADRESS, MEMORY_SIZE, VIP_FONT_ADDRESS, VIP_WORK_AREA_ADDRESS};
+
+/// Where the interpreter keeps its own things in the 4KB of memory.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Layout {
+    /// Only the font, at `0x50`. Programs can use everything past `0x200`.
+    #[default]
+    Minimal,
+    /// Like the COSMAC VIP interpreter: the stack from `0xEA0`, V0-VF at
+    /// `0xEF0` and the display at `0xF00`, all of which programs can read
+    /// and write. The VIP's digits were in its monitor ROM at `0x81xx`,
+    /// which wraps to `0x1xx` in 4KB. `0NNN` runs the 1802 machine code at
+    /// `NNN`, which is skipped with other layouts.
+    Vip,
+}
+
+impl Layout {
+    pub fn font_address(self) -> usize {
+        match self {
+            Layout::Minimal => FONT_START_ADRESS,
+            Layout::Vip => VIP_FONT_ADDRESS,
+        }
+    }
+
+    /// The end of the memory programs can be loaded into.
+    pub fn program_end(self) -> usize {
+        match self {
+            Layout::Minimal => MEMORY_SIZE,
+            Layout::Vip => VIP_WORK_AREA_ADDRESS,
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "minimal" => Ok(Layout::Minimal),
+            "vip" => Ok(Layout::Vip),
+            _ => Err(format!("unknown layout '{s}', expected minimal or vip")),
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Layout::Minimal => write!(f, "minimal"),
+            Layout::Vip => write!(f, "vip"),
+        }
+    }
+}
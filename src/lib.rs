@@ -0,0 +1,35 @@
+//! The emulator core and the frontend built on it. The core modules are public
+//! so benchmarks and fuzz targets can drive a `Chip8` without a window.
+
+pub mod asm;
+mod audio;
+pub mod cdp1802;
+pub mod chip8;
+pub mod chip8x;
+pub mod cli;
+mod config;
+pub mod consts;
+mod database;
+mod debugger;
+mod disasm;
+mod emulator;
+pub mod filters;
+pub mod font;
+pub mod frame;
+mod headless;
+pub mod instruction;
+mod keymap;
+pub mod layout;
+mod menu;
+mod osd;
+pub mod palette;
+pub mod quirks;
+mod recorder;
+mod roms;
+mod screenshot;
+mod settings;
+mod text;
+pub mod timing;
+pub mod translator;
+pub mod variant;
+mod wav;
@@ -0,0 +1,245 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use sdl2::{controller::Button, keyboard::Keycode};
+
+use crate::{
+    database::{Database, RomInfo},
+    frame::Frame,
+    palette::Rgb,
+    roms::{self, RomEntry},
+    text::{draw_text, fill_rect, shade_rect, text_width, ADVANCE, LINE_HEIGHT},
+};
+
+const TEXT: Rgb = [255, 255, 255];
+const DIM: Rgb = [150, 150, 150];
+const HIGHLIGHT: Rgb = [60, 60, 160];
+
+/// Lines at the bottom of the menu describing the selected ROM.
+const DETAIL_LINES: usize = 3;
+
+/// What the emulator should do after the menu handled an input.
+#[derive(Debug, PartialEq)]
+pub enum MenuAction {
+    None,
+    Close,
+    Load(RomEntry),
+}
+
+/// Overlay listing the ROMs in a folder or zip archive, with what the ROM
+/// database knows about the selected one.
+pub struct Menu {
+    dir: PathBuf,
+    entries: Vec<RomEntry>,
+    selected: usize,
+    database: Database,
+    /// Database lookups, made the first time a ROM is selected.
+    info: HashMap<usize, Option<RomInfo>>,
+    error: Option<String>,
+}
+
+impl Menu {
+    pub fn open(dir: &Path) -> Self {
+        // A lone file would be listed as a ROM of its own.
+        let listed = if dir.is_dir() || roms::is_zip(dir) {
+            roms::list(dir)
+        } else {
+            Err(format!("{}: not a folder or zip archive", dir.display()))
+        };
+        let (entries, error) = match listed {
+            Ok(entries) if entries.is_empty() => (entries, Some("no ROMs found".to_string())),
+            Ok(entries) => (entries, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+
+        Menu {
+            dir: dir.to_path_buf(),
+            entries,
+            selected: 0,
+            database: Database::load(),
+            info: HashMap::new(),
+            error,
+        }
+    }
+
+    /// Opens the menu with `name` selected, e.g. the ROM that is running.
+    pub fn select(&mut self, name: &str) {
+        if let Some(i) = self
+            .entries
+            .iter()
+            .position(|entry| entry.rom_name() == name)
+        {
+            self.selected = i;
+        }
+    }
+
+    pub fn key(&mut self, keycode: Keycode) -> MenuAction {
+        match keycode {
+            Keycode::UP => self.move_by(-1),
+            Keycode::DOWN => self.move_by(1),
+            Keycode::PAGEUP => self.move_by(-10),
+            Keycode::PAGEDOWN => self.move_by(10),
+            Keycode::HOME => self.selected = 0,
+            Keycode::END => self.selected = self.entries.len().saturating_sub(1),
+            Keycode::RETURN | Keycode::KP_ENTER => return self.load(),
+            Keycode::ESCAPE | Keycode::F8 => return MenuAction::Close,
+            _ => {}
+        }
+
+        MenuAction::None
+    }
+
+    pub fn button(&mut self, button: Button) -> MenuAction {
+        match button {
+            Button::DPadUp => self.move_by(-1),
+            Button::DPadDown => self.move_by(1),
+            Button::LeftShoulder => self.move_by(-10),
+            Button::RightShoulder => self.move_by(10),
+            Button::A | Button::Start => return self.load(),
+            Button::B | Button::Back => return MenuAction::Close,
+            _ => {}
+        }
+
+        MenuAction::None
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let last = self.entries.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    fn load(&self) -> MenuAction {
+        match self.entries.get(self.selected) {
+            Some(entry) => MenuAction::Load(entry.clone()),
+            None => MenuAction::None,
+        }
+    }
+
+    /// Shows why the last selection could not be loaded.
+    pub fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    fn selected_info(&mut self) -> Option<&RomInfo> {
+        let entry = self.entries.get(self.selected)?;
+        let database = &self.database;
+
+        self.info
+            .entry(self.selected)
+            .or_insert_with(|| {
+                entry
+                    .read()
+                    .ok()
+                    .and_then(|program| database.lookup(&program))
+            })
+            .as_ref()
+    }
+
+    /// Draws the menu over `frame`, sized to fit whatever scale it has.
+    pub fn draw(&mut self, frame: &mut Frame) {
+        let size = (frame.scale / 5).max(1);
+        let line = LINE_HEIGHT * size;
+        let margin = 2 * size;
+        let columns = (frame.width - 2 * margin) / (ADVANCE * size);
+        let fit = |text: &str| text.chars().take(columns).collect::<String>();
+
+        shade_rect(frame, 0, 0, frame.width, frame.height);
+
+        let title = format!("LOAD ROM - {}", self.dir.display());
+        draw_text(frame, margin, margin, &fit(&title), size, TEXT);
+
+        let details_top = frame.height - margin - DETAIL_LINES * line;
+        let list_top = margin + line + size;
+        let rows = (details_top.saturating_sub(list_top) / line).max(1);
+
+        // Keep the selection in view, roughly centred.
+        let first = self
+            .selected
+            .saturating_sub(rows / 2)
+            .min(self.entries.len().saturating_sub(rows));
+
+        for (row, i) in (first..self.entries.len()).take(rows).enumerate() {
+            let y = list_top + row * line;
+            if i == self.selected {
+                fill_rect(frame, 0, y - size, frame.width, line, HIGHLIGHT);
+            }
+            draw_text(frame, margin, y, &fit(&self.entries[i].name), size, TEXT);
+        }
+
+        let mut details = match self.error.clone() {
+            Some(error) => vec![error],
+            None => match self.selected_info() {
+                Some(info) => vec![
+                    info.title.clone(),
+                    format!("BY {}", info.authors.join(", ")),
+                    info.platform.clone().unwrap_or_default(),
+                ],
+                None => vec!["NOT IN THE ROM DATABASE".to_string()],
+            },
+        };
+        details.truncate(DETAIL_LINES);
+
+        for (row, text) in details.iter().enumerate() {
+            let y = details_top + row * line;
+            draw_text(frame, margin, y, &fit(text), size, DIM);
+        }
+
+        let hint = "ENTER LOAD  ESC BACK";
+        let x = frame.width.saturating_sub(margin + text_width(hint, size));
+        draw_text(frame, x, margin, hint, size, DIM);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    fn menu() -> Menu {
+        let dir = env::temp_dir().join("chip8-test-menu");
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["a.ch8", "b.ch8", "c.ch8"] {
+            fs::write(dir.join(name), [0x12, 0x00]).unwrap();
+        }
+
+        Menu::open(&dir)
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut menu = menu();
+
+        assert_eq!(menu.key(Keycode::UP), MenuAction::None);
+        assert_eq!(menu.selected, 0);
+        menu.key(Keycode::PAGEDOWN);
+        assert_eq!(menu.selected, 2);
+        menu.button(Button::DPadUp);
+
+        let MenuAction::Load(entry) = menu.key(Keycode::RETURN) else {
+            panic!("expected a ROM to load");
+        };
+        assert_eq!(entry.rom_name(), "b");
+        assert_eq!(menu.key(Keycode::ESCAPE), MenuAction::Close);
+    }
+
+    #[test]
+    fn test_select_and_draw() {
+        let mut menu = menu();
+        menu.select("c");
+        assert_eq!(menu.selected, 2);
+
+        let mut frame = Frame::new(640, 320, 10);
+        menu.draw(&mut frame);
+        assert!(frame.data.contains(&255));
+    }
+
+    #[test]
+    fn test_missing_folder() {
+        let mut menu = Menu::open(Path::new("/nonexistent/chip8-roms"));
+
+        assert!(menu.error.is_some());
+        assert_eq!(menu.key(Keycode::RETURN), MenuAction::None);
+    }
+}
@@ -0,0 +1,157 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    frame::Frame,
+    palette::Rgb,
+    text::{draw_text, shade_rect, text_width, LINE_HEIGHT},
+};
+
+const TEXT: Rgb = [255, 255, 255];
+const STATUS: Rgb = [255, 200, 0];
+
+/// How long a message stays on screen.
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+/// How often the FPS and IPS counters are updated.
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Text drawn over the upscaled display: short-lived messages, a status such
+/// as "PAUSED", and optionally the ROM name with FPS and IPS counters. It only
+/// draws on the frame, never on the `Chip8` display.
+pub struct Osd {
+    message: Option<(String, Instant)>,
+    pub show_stats: bool,
+    stats_start: Instant,
+    frames: u32,
+    instructions: u64,
+    fps: f32,
+    ips: f32,
+}
+
+impl Default for Osd {
+    fn default() -> Self {
+        Osd {
+            message: None,
+            show_stats: false,
+            stats_start: Instant::now(),
+            frames: 0,
+            instructions: 0,
+            fps: 0.0,
+            ips: 0.0,
+        }
+    }
+}
+
+impl Osd {
+    /// Shows `text` for a couple of seconds, replacing the previous message.
+    pub fn message(&mut self, text: impl Into<String>) {
+        self.message = Some((text.into(), Instant::now()));
+    }
+
+    /// Counts a frame shown, during which `instructions` ran.
+    pub fn tick(&mut self, instructions: u32) {
+        self.tick_at(instructions, Instant::now());
+    }
+
+    fn tick_at(&mut self, instructions: u32, now: Instant) {
+        self.frames += 1;
+        self.instructions += instructions as u64;
+
+        let elapsed = now.duration_since(self.stats_start);
+        if elapsed >= STATS_INTERVAL {
+            self.fps = self.frames as f32 / elapsed.as_secs_f32();
+            self.ips = self.instructions as f32 / elapsed.as_secs_f32();
+            self.stats_start = now;
+            self.frames = 0;
+            self.instructions = 0;
+        }
+    }
+
+    fn visible_message(&self, now: Instant) -> Option<&str> {
+        self.message
+            .as_ref()
+            .filter(|(_, shown)| now.duration_since(*shown) < MESSAGE_DURATION)
+            .map(|(text, _)| text.as_str())
+    }
+
+    /// Draws over `frame`. `status` stays in the top right corner for as long
+    /// as it is passed.
+    pub fn draw(&self, frame: &mut Frame, rom_name: &str, status: Option<&str>) {
+        let size = (frame.scale / 5).max(1);
+        let margin = 2 * size;
+        let line = LINE_HEIGHT * size;
+
+        if self.show_stats {
+            let stats = format!("{:.0} FPS  {:.0} IPS", self.fps, self.ips);
+            label(frame, margin, margin, rom_name, size, TEXT);
+            label(frame, margin, margin + line, &stats, size, TEXT);
+        }
+
+        if let Some(status) = status {
+            let x = frame
+                .width
+                .saturating_sub(margin + text_width(status, size));
+            label(frame, x, margin, status, size, STATUS);
+        }
+
+        if let Some(message) = self.visible_message(Instant::now()) {
+            let y = frame.height.saturating_sub(margin + line);
+            label(frame, margin, y, message, size, TEXT);
+        }
+    }
+}
+
+/// Text on a darkened box, so it stays readable over any game.
+fn label(frame: &mut Frame, x: usize, y: usize, text: &str, size: usize, color: Rgb) {
+    let padding = size;
+    shade_rect(
+        frame,
+        x.saturating_sub(padding),
+        y.saturating_sub(padding),
+        text_width(text, size) + padding,
+        LINE_HEIGHT * size,
+    );
+    draw_text(frame, x, y, text, size, color);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_expires() {
+        let mut osd = Osd::default();
+        osd.message("RESET");
+
+        let shown = osd.message.as_ref().unwrap().1;
+        assert_eq!(osd.visible_message(shown), Some("RESET"));
+        assert_eq!(osd.visible_message(shown + MESSAGE_DURATION), None);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut osd = Osd::default();
+        let start = osd.stats_start;
+
+        for i in 1..60 {
+            osd.tick_at(10, start + Duration::from_millis(i * 16));
+        }
+        assert_eq!(osd.fps, 0.0);
+
+        osd.tick_at(10, start + STATS_INTERVAL);
+        assert_eq!(osd.fps, 60.0);
+        assert_eq!(osd.ips, 600.0);
+    }
+
+    #[test]
+    fn test_draw_only_when_there_is_something_to_show() {
+        let mut frame = Frame::new(640, 320, 10);
+        let mut osd = Osd::default();
+
+        osd.draw(&mut frame, "pong", None);
+        assert!(!frame.data.contains(&255));
+
+        osd.show_stats = true;
+        osd.draw(&mut frame, "pong", None);
+        assert!(frame.data.contains(&255));
+    }
+}
@@ -0,0 +1,53 @@
+pub type Rgb = [u8; 3];
+
+/// Colours used to turn the monochrome CHIP-8 display into RGB pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub background: Rgb,
+    pub foreground: Rgb,
+}
+
+impl Palette {
+    pub fn new(background: Rgb, foreground: Rgb) -> Self {
+        Palette {
+            background,
+            foreground,
+        }
+    }
+
+    pub fn color(&self, pixel: bool) -> Rgb {
+        if pixel {
+            self.foreground
+        } else {
+            self.background
+        }
+    }
+}
+
+/// Parses `#rrggbb` or `#rgb`, with or without the `#`.
+pub fn parse_color(color: &str) -> Option<Rgb> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    let channel =
+        |i: usize, len: usize| u8::from_str_radix(hex.get(i * len..(i + 1) * len)?, 16).ok();
+
+    match hex.len() {
+        6 => Some([channel(0, 2)?, channel(1, 2)?, channel(2, 2)?]),
+        3 => Some([
+            channel(0, 1)? * 0x11,
+            channel(1, 1)? * 0x11,
+            channel(2, 1)? * 0x11,
+        ]),
+        _ => None,
+    }
+}
+
+/// Formats a colour as `#rrggbb`.
+pub fn format_color(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::new([1, 170, 1], [0, 255, 0])
+    }
+}
@@ -0,0 +1,110 @@
+use serde::Deserialize;
+
+use crate::consts::{
+    BNNN, DISPLAY_WAIT, MEMORY_INCREMENT_BY_X, SHIFT_USE_VY, STORE_LOAD_INCREMENTS_I, VF_RESET,
+    WRAP,
+};
+
+/// Behaviours that differ between CHIP-8 interpreters. The defaults come from
+/// the consts file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    /// `8XY1`-`8XY3` reset VF to 0.
+    pub vf_reset: bool,
+    /// `8XY6`/`8XYE` shift VY into VX instead of shifting VX in place.
+    pub shift_use_vy: bool,
+    /// `BNNN` jumps to NNN + V0 instead of XNN + VX.
+    pub bnnn: bool,
+    /// `FX55`/`FX65` leave I pointing after the last register.
+    pub store_load_increments_i: bool,
+    /// When `store_load_increments_i` is set, I is only incremented by X.
+    pub memory_increment_by_x: bool,
+    /// `DXYN` waits for the vertical blank interrupt.
+    pub display_wait: bool,
+    /// Sprites wrap around the edges of the screen instead of being clipped.
+    pub wrap: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            vf_reset: VF_RESET,
+            shift_use_vy: SHIFT_USE_VY,
+            bnnn: BNNN,
+            store_load_increments_i: STORE_LOAD_INCREMENTS_I,
+            memory_increment_by_x: MEMORY_INCREMENT_BY_X,
+            display_wait: DISPLAY_WAIT,
+            wrap: WRAP,
+        }
+    }
+}
+
+/// Quirks as named by the chip-8-database project. Every quirk is optional so
+/// that a ROM's `quirkyPlatforms` entry can override just a few of them.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseQuirks {
+    pub shift: Option<bool>,
+    pub memory_increment_by_x: Option<bool>,
+    pub memory_leave_i_unchanged: Option<bool>,
+    pub wrap: Option<bool>,
+    pub jump: Option<bool>,
+    pub vblank: Option<bool>,
+    pub logic: Option<bool>,
+}
+
+impl Quirks {
+    pub fn apply(&mut self, quirks: &DatabaseQuirks) {
+        if let Some(shift) = quirks.shift {
+            self.shift_use_vy = !shift;
+        }
+        if let Some(increment_by_x) = quirks.memory_increment_by_x {
+            self.memory_increment_by_x = increment_by_x;
+        }
+        if let Some(leave_i_unchanged) = quirks.memory_leave_i_unchanged {
+            self.store_load_increments_i = !leave_i_unchanged;
+        }
+        if let Some(wrap) = quirks.wrap {
+            self.wrap = wrap;
+        }
+        if let Some(jump) = quirks.jump {
+            self.bnnn = !jump;
+        }
+        if let Some(vblank) = quirks.vblank {
+            self.display_wait = vblank;
+        }
+        if let Some(logic) = quirks.logic {
+            self.vf_reset = logic;
+        }
+    }
+
+    /// Every quirk with the name `set` knows it by.
+    pub fn named(&self) -> [(&'static str, bool); 7] {
+        [
+            ("vf_reset", self.vf_reset),
+            ("shift_use_vy", self.shift_use_vy),
+            ("bnnn", self.bnnn),
+            ("store_load_increments_i", self.store_load_increments_i),
+            ("memory_increment_by_x", self.memory_increment_by_x),
+            ("display_wait", self.display_wait),
+            ("wrap", self.wrap),
+        ]
+    }
+
+    /// Sets a quirk by its field name, e.g. `wrap` or `display-wait`.
+    pub fn set(&mut self, name: &str, value: bool) -> Result<(), String> {
+        let quirk = match name.replace('-', "_").as_str() {
+            "vf_reset" => &mut self.vf_reset,
+            "shift_use_vy" => &mut self.shift_use_vy,
+            "bnnn" => &mut self.bnnn,
+            "store_load_increments_i" => &mut self.store_load_increments_i,
+            "memory_increment_by_x" => &mut self.memory_increment_by_x,
+            "display_wait" => &mut self.display_wait,
+            "wrap" => &mut self.wrap,
+            _ => return Err(format!("unknown quirk '{name}'")),
+        };
+        *quirk = value;
+
+        Ok(())
+    }
+}
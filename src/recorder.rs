@@ -0,0 +1,244 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    audio::{AudioSettings, Beeper, SAMPLE_RATE},
+    chip8::Chip8,
+    chip8x::{tone_frequency, BACKGROUND_COLORS, FOREGROUND_COLORS},
+    consts::WIDTH,
+    filters::Filters,
+    frame::Frame,
+    palette::Palette,
+    wav::WavWriter,
+};
+
+/// Beeper samples produced for every 60Hz tick.
+const SAMPLES_PER_FRAME: usize = SAMPLE_RATE as usize / 60;
+
+/// Records one frame per 60Hz tick, either as an animated GIF, as a raw Y4M
+/// video with the beeper written to a separate WAV file, or as the beeper
+/// alone.
+pub enum Recorder {
+    Gif(GifRecorder),
+    Video(Y4mRecorder, WavRecorder),
+    Audio(WavRecorder),
+}
+
+impl Recorder {
+    /// Starts a GIF recording of a display `rows` pixels high.
+    pub fn gif(path: &Path, palette: &Palette, scale: usize, rows: usize) -> io::Result<Self> {
+        Ok(Recorder::Gif(GifRecorder::create(
+            path, palette, scale, rows,
+        )?))
+    }
+
+    /// Starts a Y4M recording of a display `rows` pixels high at `path` and a
+    /// WAV recording next to it.
+    pub fn video(
+        path: &Path,
+        palette: &Palette,
+        filters: &Filters,
+        scale: usize,
+        rows: usize,
+        audio: &AudioSettings,
+    ) -> io::Result<Self> {
+        Ok(Recorder::Video(
+            Y4mRecorder::create(path, palette, filters, scale, rows)?,
+            WavRecorder::create(&path.with_extension("wav"), audio)?,
+        ))
+    }
+
+    pub fn audio(path: &Path, audio: &AudioSettings) -> io::Result<Self> {
+        Ok(Recorder::Audio(WavRecorder::create(path, audio)?))
+    }
+
+    pub fn capture(&mut self, chip8: &Chip8) -> io::Result<()> {
+        match self {
+            Recorder::Gif(gif) => gif.capture(chip8),
+            Recorder::Video(video, audio) => {
+                video.capture(chip8)?;
+                audio.capture(chip8)
+            }
+            Recorder::Audio(audio) => audio.capture(chip8),
+        }
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Recorder::Gif(gif) => gif.finish(),
+            Recorder::Video(video, audio) => {
+                video.finish()?;
+                audio.finish()
+            }
+            Recorder::Audio(audio) => audio.finish(),
+        }
+    }
+}
+
+pub struct GifRecorder {
+    encoder: gif::Encoder<BufWriter<File>>,
+    scale: usize,
+    frames: u64,
+}
+
+impl GifRecorder {
+    pub fn create(path: &Path, palette: &Palette, scale: usize, rows: usize) -> io::Result<Self> {
+        // The palette, then the colour board's colours for CHIP-8X.
+        let global_palette = [palette.background, palette.foreground]
+            .iter()
+            .chain(&FOREGROUND_COLORS)
+            .chain(&BACKGROUND_COLORS)
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        let mut encoder = gif::Encoder::new(
+            BufWriter::new(File::create(path)?),
+            (WIDTH * scale) as u16,
+            (rows * scale) as u16,
+            &global_palette,
+        )
+        .map_err(io::Error::other)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(io::Error::other)?;
+
+        Ok(GifRecorder {
+            encoder,
+            scale,
+            frames: 0,
+        })
+    }
+
+    pub fn capture(&mut self, chip8: &Chip8) -> io::Result<()> {
+        let (display, scale) = (&chip8.display, self.scale);
+        let (width, height) = (WIDTH * scale, display.len() * scale);
+        let pixels: Vec<u8> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x / scale, y / scale)))
+            .map(|(x, y)| match (chip8.colors(), display[y][x]) {
+                (None, pixel) => pixel as u8,
+                (Some(colors), true) => 2 + colors.foreground(x, y),
+                (Some(colors), false) => 2 + FOREGROUND_COLORS.len() as u8 + colors.background,
+            })
+            .collect();
+
+        let mut frame = gif::Frame::from_indexed_pixels(width as u16, height as u16, pixels, None);
+        // GIF delays are in hundredths of a second, so spread the 60Hz ticks over
+        // 1 and 2 centisecond delays that add up to the right duration.
+        frame.delay = ((self.frames + 1) * 100 / 60 - self.frames * 100 / 60) as u16;
+        self.frames += 1;
+
+        self.encoder.write_frame(&frame).map_err(io::Error::other)
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        self.encoder.into_inner().map_err(io::Error::other)?.flush()
+    }
+}
+
+/// Uncompressed YUV4MPEG2 stream (4:4:4, 60 fps) of the upscaled and filtered
+/// display.
+pub struct Y4mRecorder {
+    writer: BufWriter<File>,
+    palette: Palette,
+    filters: Filters,
+    scale: usize,
+}
+
+impl Y4mRecorder {
+    pub fn create(
+        path: &Path,
+        palette: &Palette,
+        filters: &Filters,
+        scale: usize,
+        rows: usize,
+    ) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "YUV4MPEG2 W{} H{} F60:1 Ip A1:1 C444",
+            WIDTH * scale,
+            rows * scale
+        )?;
+
+        Ok(Y4mRecorder {
+            writer,
+            palette: *palette,
+            filters: *filters,
+            scale,
+        })
+    }
+
+    pub fn capture(&mut self, chip8: &Chip8) -> io::Result<()> {
+        let mut frame = Frame::from_chip8(chip8, &self.palette, self.scale);
+        self.filters.apply(&mut frame);
+
+        let pixels = frame.data.chunks_exact(3);
+        let mut planes = [
+            Vec::with_capacity(pixels.len()),
+            Vec::with_capacity(pixels.len()),
+            Vec::with_capacity(pixels.len()),
+        ];
+        for pixel in pixels {
+            let [y, u, v] = rgb_to_yuv([pixel[0], pixel[1], pixel[2]]);
+            planes[0].push(y);
+            planes[1].push(u);
+            planes[2].push(v);
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+        for plane in planes {
+            self.writer.write_all(&plane)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// BT.601 studio-swing conversion, which is what Y4M consumers assume.
+fn rgb_to_yuv([r, g, b]: [u8; 3]) -> [u8; 3] {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+
+    let y = 16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0;
+    let u = 128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0;
+    let v = 128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0;
+
+    [y.round() as u8, u.round() as u8, v.round() as u8]
+}
+
+/// Renders the beeper into a WAV file, one 60Hz tick at a time.
+pub struct WavRecorder {
+    writer: WavWriter,
+    beeper: Beeper,
+    buffer: Vec<f32>,
+}
+
+impl WavRecorder {
+    pub fn create(path: &Path, audio: &AudioSettings) -> io::Result<Self> {
+        Ok(WavRecorder {
+            writer: WavWriter::create(path, SAMPLE_RATE as u32)?,
+            beeper: Beeper::new(*audio, SAMPLE_RATE),
+            buffer: vec![0.0; SAMPLES_PER_FRAME],
+        })
+    }
+
+    pub fn capture(&mut self, chip8: &Chip8) -> io::Result<()> {
+        if let Some(value) = chip8.output_port {
+            self.beeper.set_frequency(tone_frequency(value));
+        }
+        self.beeper.set_gate(chip8.sound_timer > 0);
+        self.beeper.fill(&mut self.buffer);
+
+        self.writer.write_samples(&self.buffer)
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        self.writer.finish()
+    }
+}
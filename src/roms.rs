@@ -0,0 +1,252 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use zip::ZipArchive;
+
+use crate::chip8::{read_program, LoadError};
+
+/// Extensions of CHIP-8, SUPER-CHIP and XO-CHIP programs.
+pub const ROM_EXTENSIONS: [&str; 3] = ["ch8", "sc8", "xo8"];
+
+/// A ROM found on disk or inside a zip archive, not read yet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RomEntry {
+    /// Path of the file, or of the entry inside the archive.
+    pub name: String,
+    source: RomSource,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum RomSource {
+    File(PathBuf),
+    Zip(PathBuf),
+}
+
+fn is_rom(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            ROM_EXTENSIONS
+                .iter()
+                .any(|rom| rom.eq_ignore_ascii_case(extension))
+        })
+}
+
+pub fn is_zip(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+/// Lists the ROMs at `path`: every ROM in a directory (not recursing into
+/// subdirectories), every ROM in a zip archive, or the file itself.
+pub fn list(path: &Path) -> Result<Vec<RomEntry>, String> {
+    let error = |e: String| format!("{}: {e}", path.display());
+
+    let mut entries = if path.is_dir() {
+        list_dir(path).map_err(|e| error(e.to_string()))?
+    } else if is_zip(path) {
+        list_zip(path).map_err(error)?
+    } else {
+        vec![RomEntry {
+            name: path.display().to_string(),
+            source: RomSource::File(path.to_path_buf()),
+        }]
+    };
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(entries)
+}
+
+fn list_dir(dir: &Path) -> io::Result<Vec<RomEntry>> {
+    let mut entries = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && is_rom(&path) {
+            entries.push(RomEntry {
+                name: path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                source: RomSource::File(path),
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+fn list_zip(path: &Path) -> Result<Vec<RomEntry>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut archive = ZipArchive::new(file).map_err(|e| e.to_string())?;
+
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i).map_err(|e| e.to_string())?;
+        if entry.is_file() && is_rom(Path::new(entry.name())) {
+            entries.push(RomEntry {
+                name: entry.name().to_string(),
+                source: RomSource::Zip(path.to_path_buf()),
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+impl RomEntry {
+    /// The file name without directories or extension, which keys the
+    /// per-ROM keymap and config tables.
+    pub fn rom_name(&self) -> String {
+        Path::new(&self.name)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "chip8".to_string())
+    }
+
+    /// Reads the program. Whether it fits in memory depends on the load
+    /// address, so that is only checked when it is loaded.
+    pub fn read(&self) -> Result<Vec<u8>, String> {
+        let program = match &self.source {
+            RomSource::File(path) => File::open(path)
+                .map_err(LoadError::from)
+                .and_then(|file| read_program(file, 0)),
+            RomSource::Zip(archive) => {
+                let file = File::open(archive).map_err(|e| e.to_string())?;
+                let mut archive = ZipArchive::new(file).map_err(|e| e.to_string())?;
+                let entry = archive.by_name(&self.name).map_err(|e| e.to_string())?;
+                read_program(entry, 0)
+            }
+        };
+
+        program.map_err(|e| format!("{}: {e}", self.name))
+    }
+}
+
+/// Picks a ROM from `entries`: the only one there is, the one named by
+/// `select` (a name, a file name or a 1-based number), or one chosen from a
+/// numbered list written to `output` with the answer read from `input`.
+pub fn choose<'a>(
+    entries: &'a [RomEntry],
+    select: Option<&str>,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<&'a RomEntry, String> {
+    if let Some(select) = select {
+        return find(entries, select).ok_or_else(|| format!("no ROM matches '{select}'"));
+    }
+
+    match entries {
+        [] => return Err("no ROMs found".to_string()),
+        [entry] => return Ok(entry),
+        _ => {}
+    }
+
+    for (i, entry) in entries.iter().enumerate() {
+        writeln!(output, "{:>3}) {}", i + 1, entry.name).map_err(|e| e.to_string())?;
+    }
+
+    loop {
+        write!(output, "select a ROM [1-{}]: ", entries.len()).map_err(|e| e.to_string())?;
+        output.flush().map_err(|e| e.to_string())?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return Err("no ROM selected".to_string());
+        }
+        match find(entries, line.trim()) {
+            Some(entry) => return Ok(entry),
+            None => {
+                writeln!(output, "no ROM matches '{}'", line.trim()).map_err(|e| e.to_string())?
+            }
+        }
+    }
+}
+
+fn find<'a>(entries: &'a [RomEntry], select: &str) -> Option<&'a RomEntry> {
+    if let Ok(number) = select.parse::<usize>() {
+        return entries.get(number.checked_sub(1)?);
+    }
+
+    entries.iter().find(|entry| {
+        entry.name == select
+            || Path::new(&entry.name).file_name() == Some(select.as_ref())
+            || entry.rom_name() == select
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    fn zip_with(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, contents) in files {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_single_rom_in_zip() {
+        let path = env::temp_dir().join("chip8-test-single.zip");
+        zip_with(
+            &path,
+            &[("readme.txt", b"hello"), ("games/pong.ch8", &[0x12, 0x00])],
+        );
+
+        let entries = list(&path).unwrap();
+        let entry = choose(&entries, None, &mut io::empty(), &mut io::sink()).unwrap();
+
+        assert_eq!(entry.name, "games/pong.ch8");
+        assert_eq!(entry.rom_name(), "pong");
+        assert_eq!(entry.read().unwrap(), [0x12, 0x00]);
+    }
+
+    #[test]
+    fn test_choose_from_list() {
+        let path = env::temp_dir().join("chip8-test-pack.zip");
+        zip_with(
+            &path,
+            &[("tetris.ch8", &[0x00, 0xE0]), ("blitz.sc8", &[0x12, 0x00])],
+        );
+        let entries = list(&path).unwrap();
+
+        let mut output = Vec::new();
+        let entry = choose(&entries, None, &mut "7\n2\n".as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert_eq!(entry.name, "tetris.ch8");
+        assert!(output.starts_with("  1) blitz.sc8\n  2) tetris.ch8\n"));
+        assert!(output.contains("no ROM matches '7'"));
+
+        let entry = choose(&entries, Some("blitz"), &mut io::empty(), &mut io::sink()).unwrap();
+        assert_eq!(entry.name, "blitz.sc8");
+        assert!(choose(&entries, None, &mut io::empty(), &mut io::sink()).is_err());
+    }
+
+    #[test]
+    fn test_directory_lists_roms_only() {
+        let dir = env::temp_dir().join("chip8-test-roms");
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["b.xo8", "a.CH8", "notes.txt"] {
+            fs::write(dir.join(name), [0x00, 0xE0]).unwrap();
+        }
+
+        let names: Vec<_> = list(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+
+        assert_eq!(names, ["a.CH8", "b.xo8"]);
+    }
+}
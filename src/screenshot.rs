@@ -0,0 +1,105 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{chip8::Chip8, filters::Filters, frame::Frame, palette::Palette};
+
+/// Writes the display at its native resolution, one PNG pixel per CHIP-8 pixel.
+pub fn save_native(chip8: &Chip8, palette: &Palette, path: &Path) -> io::Result<()> {
+    save_png(&Frame::from_chip8(chip8, palette, 1), path)
+}
+
+/// Writes the display the way it is presented: upscaled and post-processed.
+pub fn save_scaled(
+    chip8: &Chip8,
+    palette: &Palette,
+    filters: &Filters,
+    scale: usize,
+    path: &Path,
+) -> io::Result<()> {
+    let mut frame = Frame::from_chip8(chip8, palette, scale);
+    filters.apply(&mut frame);
+
+    save_png(&frame, path)
+}
+
+pub fn save_png(frame: &Frame, path: &Path) -> io::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+
+    let mut encoder = png::Encoder::new(writer, frame.width as u32, frame.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer
+        .write_image_data(&frame.data)
+        .map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+/// Builds a file name like `pong-20250101-120000.png` in `dir`.
+pub fn timestamped_path(dir: &Path, rom_name: &str, suffix: &str, extension: &str) -> PathBuf {
+    dir.join(format!("{rom_name}-{}{suffix}.{extension}", timestamp()))
+}
+
+/// Current UTC time formatted as `YYYYMMDD-HHMMSS`.
+fn timestamp() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, time) = (seconds / 86400, seconds % 86400);
+
+    // Days since the epoch to a civil date, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        time / 3600,
+        (time / 60) % 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_native_screenshot_matches_display_size() {
+        let mut chip8 = Chip8::new();
+        chip8.display[0][0] = true;
+        let path = env::temp_dir().join("chip8-test-native.png");
+
+        save_native(&chip8, &Palette::default(), &path).unwrap();
+
+        let decoder = png::Decoder::new(io::BufReader::new(File::open(&path).unwrap()));
+        let reader = decoder.read_info().unwrap();
+        assert_eq!(reader.info().width, 64);
+        assert_eq!(reader.info().height, 32);
+    }
+
+    #[test]
+    fn test_timestamped_path() {
+        let path = timestamped_path(Path::new("shots"), "pong", "-native", "png");
+        let name = path.file_name().unwrap().to_string_lossy();
+
+        assert!(path.starts_with("shots"));
+        assert!(name.starts_with("pong-"));
+        assert!(name.ends_with("-native.png"));
+        assert_eq!(name.len(), "pong-YYYYMMDD-HHMMSS-native.png".len());
+    }
+}
@@ -0,0 +1,206 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use crate::{
+    audio::AudioSettings,
+    chip8::{Chip8, LoadError},
+    consts::{
+        FAST_FORWARD_MULTIPLIER, INSTRUCTIONS_PER_SECOND, PROGRAM_START_ADDRESS, SCALE, STACK_DEPTH,
+    },
+    database::RomInfo,
+    font::Font,
+    keymap::Keymap,
+    layout::Layout,
+    palette::Palette,
+    quirks::Quirks,
+    timing::{FrameClock, Timing},
+    variant::Variant,
+};
+
+/// Everything about a run that can differ from game to game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub quirks: Quirks,
+    /// Ignored with [`Timing::Vip`], where instructions take as long as
+    /// they did on the VIP.
+    pub instructions_per_second: u32,
+    pub timing: Timing,
+    pub stack_depth: usize,
+    /// Keeps return addresses in memory where the VIP did.
+    pub memory_stack: bool,
+    pub layout: Layout,
+    pub font: Font,
+    pub variant: Variant,
+    pub scale: usize,
+    pub palette: Palette,
+    pub audio: AudioSettings,
+    pub keymap: Keymap,
+    /// Seed for `CXNN`, random on every run when not set.
+    pub seed: Option<u64>,
+    /// Where programs are loaded and start running.
+    pub load_address: usize,
+    /// Folder or zip archive listed by the in-window ROM menu.
+    pub rom_dir: Option<PathBuf>,
+    pub fast_forward: FastForward,
+    /// Silences the beeper while fast-forwarding. Otherwise beeps keep their
+    /// pitch and only get shorter.
+    pub mute_fast_forward: bool,
+}
+
+/// How fast the game runs while fast-forwarding.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FastForward {
+    /// This many 60Hz frames per frame shown.
+    Multiplier(u32),
+    /// As many frames as the host can run.
+    Uncapped,
+}
+
+impl FastForward {
+    /// The next speed to cycle through: 2x, 4x, 8x, 16x, uncapped.
+    pub fn next(self) -> Self {
+        match self {
+            FastForward::Multiplier(m) if m < 16 => {
+                FastForward::Multiplier((m + 1).next_power_of_two().max(2))
+            }
+            FastForward::Multiplier(_) => FastForward::Uncapped,
+            FastForward::Uncapped => FastForward::Multiplier(2),
+        }
+    }
+}
+
+impl FromStr for FastForward {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("uncapped") {
+            return Ok(FastForward::Uncapped);
+        }
+
+        match s.trim_end_matches(['x', 'X']).parse() {
+            Ok(multiplier) if multiplier >= 1 => Ok(FastForward::Multiplier(multiplier)),
+            _ => Err(format!(
+                "invalid fast-forward speed '{s}', expected a multiplier or 'uncapped'"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FastForward {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FastForward::Multiplier(multiplier) => write!(f, "{multiplier}x"),
+            FastForward::Uncapped => write!(f, "uncapped"),
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            quirks: Quirks::default(),
+            instructions_per_second: INSTRUCTIONS_PER_SECOND,
+            timing: Timing::default(),
+            stack_depth: STACK_DEPTH,
+            memory_stack: false,
+            layout: Layout::default(),
+            font: Font::default(),
+            variant: Variant::default(),
+            scale: SCALE,
+            palette: Palette::default(),
+            audio: AudioSettings::default(),
+            keymap: Keymap::default(),
+            seed: None,
+            load_address: PROGRAM_START_ADDRESS,
+            rom_dir: None,
+            fast_forward: FastForward::Multiplier(FAST_FORWARD_MULTIPLIER),
+            mute_fast_forward: true,
+        }
+    }
+}
+
+impl Settings {
+    /// The number of instructions run between two 60Hz ticks.
+    pub fn cycles_per_frame(&self) -> u32 {
+        (self.instructions_per_second / 60).max(1)
+    }
+
+    /// Decides when the frames of `chip8` end.
+    pub fn frame_clock(&self, chip8: &Chip8) -> FrameClock {
+        match self.timing {
+            Timing::Fixed => FrameClock::fixed(self.cycles_per_frame()),
+            Timing::Vip => FrameClock::vip(chip8),
+        }
+    }
+
+    /// A `Chip8` with the quirks, timing, stack, layout, font, variant and
+    /// seed of these settings.
+    pub fn chip8(&self) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.quirks = self.quirks;
+        chip8.timing = self.timing;
+        chip8.stack_depth = self.stack_depth;
+        chip8.memory_stack = self.memory_stack;
+        chip8.set_layout(self.layout);
+        chip8.set_font(self.font.clone());
+        chip8.set_variant(self.variant);
+        if let Some(seed) = self.seed {
+            chip8.seed(seed);
+        }
+        chip8
+    }
+
+    /// A `Chip8` from [`Settings::chip8`] with `program` loaded.
+    pub fn load(&self, program: &[u8]) -> Result<Chip8, LoadError> {
+        let mut chip8 = self.chip8();
+        chip8.load_program_at(program, self.load_address)?;
+        Ok(chip8)
+    }
+
+    /// Also moves programs to where the variant's interpreter loaded them.
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+        self.load_address = variant.load_address();
+    }
+
+    pub fn apply_rom_info(&mut self, info: &RomInfo) {
+        self.quirks = info.quirks;
+        if let Some(tickrate) = info.tickrate {
+            self.instructions_per_second = tickrate * 60;
+        }
+        if let Some(stack_depth) = info.stack_depth {
+            self.stack_depth = stack_depth;
+        }
+        if let Some(font) = info.font {
+            self.font = Font::preset(font);
+        }
+        if let Some(variant) = info.variant {
+            self.set_variant(variant);
+        }
+        if let Some(palette) = info.palette {
+            self.palette = palette;
+        }
+        self.keymap.apply_hints(&info.keys);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fast_forward() {
+        assert_eq!("3".parse(), Ok(FastForward::Multiplier(3)));
+        assert_eq!("8x".parse(), Ok(FastForward::Multiplier(8)));
+        assert_eq!("Uncapped".parse(), Ok(FastForward::Uncapped));
+        assert!("0".parse::<FastForward>().is_err());
+        assert!("fast".parse::<FastForward>().is_err());
+
+        let mut speed = FastForward::Multiplier(3);
+        let mut speeds = Vec::new();
+        for _ in 0..5 {
+            speed = speed.next();
+            speeds.push(speed.to_string());
+        }
+        assert_eq!(speeds, ["4x", "8x", "16x", "uncapped", "2x"]);
+    }
+}
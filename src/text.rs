@@ -0,0 +1,161 @@
+use crate::{frame::Frame, palette::Rgb};
+
+pub const GLYPH_WIDTH: usize = 3;
+pub const GLYPH_HEIGHT: usize = 5;
+/// Distance from the start of one character to the next, in font pixels.
+pub const ADVANCE: usize = GLYPH_WIDTH + 1;
+/// Distance from the top of one line to the next, in font pixels.
+pub const LINE_HEIGHT: usize = GLYPH_HEIGHT + 2;
+
+/// A 3x5 font for overlays, one row per byte with the leftmost pixel in bit 2.
+/// Lowercase letters are drawn as uppercase and anything missing as `?`.
+const GLYPHS: [(char, [u8; GLYPH_HEIGHT]); 64] = [
+    (' ', [0b000, 0b000, 0b000, 0b000, 0b000]),
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('G', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('I', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('J', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('K', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('L', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('M', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('N', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('O', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('Q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('S', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('U', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('W', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('Z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b110, 0b001, 0b010, 0b100, 0b111]),
+    ('3', [0b110, 0b001, 0b010, 0b001, 0b110]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b110, 0b001, 0b110]),
+    ('6', [0b011, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b010, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b110]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    (',', [0b000, 0b000, 0b000, 0b010, 0b100]),
+    (':', [0b000, 0b010, 0b000, 0b010, 0b000]),
+    (';', [0b000, 0b010, 0b000, 0b010, 0b100]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('+', [0b000, 0b010, 0b111, 0b010, 0b000]),
+    ('_', [0b000, 0b000, 0b000, 0b000, 0b111]),
+    ('=', [0b000, 0b111, 0b000, 0b111, 0b000]),
+    ('/', [0b001, 0b001, 0b010, 0b100, 0b100]),
+    ('\\', [0b100, 0b100, 0b010, 0b001, 0b001]),
+    ('(', [0b001, 0b010, 0b010, 0b010, 0b001]),
+    (')', [0b100, 0b010, 0b010, 0b010, 0b100]),
+    ('[', [0b011, 0b010, 0b010, 0b010, 0b011]),
+    (']', [0b110, 0b010, 0b010, 0b010, 0b110]),
+    ('<', [0b001, 0b010, 0b100, 0b010, 0b001]),
+    ('>', [0b100, 0b010, 0b001, 0b010, 0b100]),
+    ('!', [0b010, 0b010, 0b010, 0b000, 0b010]),
+    ('?', [0b110, 0b001, 0b010, 0b000, 0b010]),
+    ('\'', [0b010, 0b010, 0b000, 0b000, 0b000]),
+    ('"', [0b101, 0b101, 0b000, 0b000, 0b000]),
+    ('#', [0b101, 0b111, 0b101, 0b111, 0b101]),
+    ('%', [0b101, 0b001, 0b010, 0b100, 0b101]),
+    ('&', [0b010, 0b101, 0b010, 0b101, 0b011]),
+    ('*', [0b101, 0b010, 0b101, 0b000, 0b000]),
+    ('@', [0b010, 0b101, 0b111, 0b100, 0b011]),
+    ('$', [0b011, 0b110, 0b010, 0b011, 0b110]),
+    ('^', [0b010, 0b101, 0b000, 0b000, 0b000]),
+];
+
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    let find = |c: char| GLYPHS.iter().find(|(glyph, _)| *glyph == c);
+
+    find(c.to_ascii_uppercase())
+        .or_else(|| find('?'))
+        .map(|(_, rows)| *rows)
+        .unwrap_or_default()
+}
+
+/// Width of `text` in frame pixels when drawn at `size` frame pixels per font
+/// pixel.
+pub fn text_width(text: &str, size: usize) -> usize {
+    text.chars().count() * ADVANCE * size
+}
+
+/// Draws `text` with its top left corner at `x`, `y`, clipped to the frame.
+pub fn draw_text(frame: &mut Frame, x: usize, y: usize, text: &str, size: usize, color: Rgb) {
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i * ADVANCE * size;
+        if left >= frame.width {
+            break;
+        }
+
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (0b100 >> column) != 0 {
+                    fill_rect(
+                        frame,
+                        left + column * size,
+                        y + row * size,
+                        size,
+                        size,
+                        color,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Fills a rectangle, clipped to the frame.
+pub fn fill_rect(frame: &mut Frame, x: usize, y: usize, width: usize, height: usize, color: Rgb) {
+    for y in y..(y + height).min(frame.height) {
+        for x in x..(x + width).min(frame.width) {
+            frame.set_pixel(x, y, color);
+        }
+    }
+}
+
+/// Darkens a rectangle so text drawn over the game stays readable.
+pub fn shade_rect(frame: &mut Frame, x: usize, y: usize, width: usize, height: usize) {
+    for y in y..(y + height).min(frame.height) {
+        for x in x..(x + width).min(frame.width) {
+            let pixel = frame.pixel(x, y);
+            frame.set_pixel(x, y, pixel.map(|channel| channel / 4));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glyph_fallbacks() {
+        assert_eq!(glyph('a'), glyph('A'));
+        assert_eq!(glyph('é'), glyph('?'));
+        assert_ne!(glyph('O'), glyph('0'));
+        assert_ne!(glyph('#'), glyph('?'));
+    }
+
+    #[test]
+    fn test_draw_text_is_clipped() {
+        let mut frame = Frame::new(10, 4, 1);
+        draw_text(&mut frame, 0, 0, "HI!", 1, [255, 255, 255]);
+
+        // The left column of the H, but only the rows that fit.
+        assert_eq!(frame.pixel(0, 0), [255, 255, 255]);
+        assert_eq!(frame.pixel(0, 3), [255, 255, 255]);
+        assert_eq!(frame.pixel(1, 0), [0, 0, 0]);
+        assert_eq!(text_width("HI!", 2), 24);
+    }
+}
@@ -0,0 +1,273 @@
+use std::{fmt, str::FromStr};
+
+use crate::chip8::Chip8;
+
+/// Machine cycles of a COSMAC VIP in one 60Hz frame: a 1.76MHz clock with 8
+/// clock cycles per machine cycle.
+pub const VIP_CYCLES_PER_FRAME: u64 = 3668;
+/// Machine cycles of every frame taken by the display interrupt and its DMA,
+/// which the interpreter does not get to use.
+pub const VIP_INTERRUPT_CYCLES: u64 = 1832;
+
+/// Machine cycles the interpreter spends fetching and decoding an instruction
+/// before running it.
+const FETCH_CYCLES: u32 = 40;
+
+/// How instructions are spread over 60Hz frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Timing {
+    /// The same number of instructions every frame, whatever they are.
+    #[default]
+    Fixed,
+    /// Each instruction takes as long as it did on the COSMAC VIP, so a frame
+    /// runs as many instructions as the VIP would have.
+    Vip,
+}
+
+impl FromStr for Timing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fixed" => Ok(Timing::Fixed),
+            "vip" => Ok(Timing::Vip),
+            _ => Err(format!("unknown timing '{s}', expected fixed or vip")),
+        }
+    }
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Timing::Fixed => write!(f, "fixed"),
+            Timing::Vip => write!(f, "vip"),
+        }
+    }
+}
+
+/// Machine cycles the COSMAC VIP interpreter takes for `instruction`, rounded
+/// from Laurence Scotford's disassembly of it. `vx` is VX before the
+/// instruction ran and `skipped` whether it skipped the next one.
+pub fn vip_cycles(instruction: u16, vx: u8, skipped: bool) -> u32 {
+    let x = (instruction >> 8) & 0xF;
+    let n = (instruction & 0xF) as u32;
+    let skip = if skipped { 4 } else { 0 };
+
+    FETCH_CYCLES
+        + match instruction >> 12 {
+            0x0 => match instruction {
+                // Clearing loops over all 256 bytes of display memory.
+                0x00E0 => 3078,
+                0x00EE => 10,
+                _ => 0,
+            },
+            0x1 | 0xA => 12,
+            0x2 => 26,
+            0x3 | 0x4 => 10 + skip,
+            0x5 | 0x9 => 14 + skip,
+            0x6 => 6,
+            0x7 => 10,
+            0x8 => match n {
+                0x0 => 12,
+                _ => 44,
+            },
+            0xB => 22,
+            0xC => 36,
+            0xD => draw_cycles(vx, n),
+            0xE => 14 + skip,
+            0xF => match instruction & 0xFF {
+                0x0A => 18,
+                0x1E | 0x29 => 16,
+                // A subtraction per unit counted in each digit.
+                0x33 => {
+                    let digits = [vx / 100, (vx / 10) % 10, vx % 10];
+                    80 + 16 * digits.iter().map(|&digit| digit as u32).sum::<u32>()
+                }
+                0x55 | 0x65 => 14 + 14 * (x as u32 + 1),
+                _ => 10,
+            },
+            _ => 0,
+        }
+}
+
+/// `DXYN` shifts each sprite row right by X mod 8 bits and then XORs one
+/// display byte, or two when the sprite is not byte aligned.
+fn draw_cycles(x: u8, rows: u32) -> u32 {
+    let shift = (x % 8) as u32;
+    let row = match shift {
+        0 => 34,
+        _ => 54 + 4 * shift,
+    };
+
+    26 + rows * row
+}
+
+/// Decides when a frame ends: after a fixed number of instructions, or once
+/// the instructions ran for as many machine cycles as a VIP frame leaves the
+/// interpreter.
+#[derive(Clone, Copy, Debug)]
+pub enum FrameClock {
+    Fixed {
+        cycles_per_frame: u32,
+        cycle_in_frame: u32,
+    },
+    Vip {
+        /// `Chip8::machine_cycles` at the start of the frame.
+        frame_start: u64,
+    },
+}
+
+impl FrameClock {
+    pub fn fixed(cycles_per_frame: u32) -> Self {
+        FrameClock::Fixed {
+            cycles_per_frame: cycles_per_frame.max(1),
+            cycle_in_frame: 0,
+        }
+    }
+
+    pub fn vip(chip8: &Chip8) -> Self {
+        FrameClock::Vip {
+            frame_start: chip8.machine_cycles(),
+        }
+    }
+
+    /// Instructions left in the frame, when that is known ahead of time.
+    pub fn remaining(&self) -> Option<u32> {
+        match self {
+            FrameClock::Fixed {
+                cycles_per_frame,
+                cycle_in_frame,
+            } => Some(cycles_per_frame - cycle_in_frame),
+            FrameClock::Vip { .. } => None,
+        }
+    }
+
+    /// Accounts for `cycles` instructions run without ticking, fewer than
+    /// [`FrameClock::remaining`].
+    pub fn advance(&mut self, cycles: u32) {
+        if let FrameClock::Fixed { cycle_in_frame, .. } = self {
+            *cycle_in_frame += cycles;
+        }
+    }
+
+    /// Accounts for the instruction `chip8` just ran. Returns whether it
+    /// ended the frame.
+    pub fn tick(&mut self, chip8: &Chip8) -> bool {
+        match self {
+            FrameClock::Fixed {
+                cycles_per_frame,
+                cycle_in_frame,
+            } => {
+                *cycle_in_frame += 1;
+                if *cycle_in_frame < *cycles_per_frame {
+                    return false;
+                }
+
+                *cycle_in_frame = 0;
+                true
+            }
+            FrameClock::Vip { frame_start } => {
+                // A draw waiting for the interrupt idles until the next frame.
+                if chip8.waiting_for_vblank() {
+                    *frame_start = chip8.machine_cycles();
+                    return true;
+                }
+
+                let budget = VIP_CYCLES_PER_FRAME - VIP_INTERRUPT_CYCLES;
+                if chip8.machine_cycles() - *frame_start < budget {
+                    return false;
+                }
+
+                // What an instruction ran over eats into the next frame.
+                *frame_start += budget;
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vip_cycles() {
+        assert_eq!(vip_cycles(0x6012, 0, false), 46);
+        assert_eq!(vip_cycles(0x3012, 0x12, true), 54);
+        assert_eq!(vip_cycles(0x3012, 0x00, false), 50);
+        assert_eq!(vip_cycles(0xF233, 255, false), 40 + 80 + 16 * 12);
+
+        // Unaligned sprites cost more, and more the further they are shifted.
+        let aligned = vip_cycles(0xD015, 8, false);
+        assert!(aligned < vip_cycles(0xD015, 9, false));
+        assert!(vip_cycles(0xD015, 9, false) < vip_cycles(0xD015, 15, false));
+    }
+
+    #[test]
+    fn test_vip_frames_run_less_of_slow_instructions() {
+        let frame = |program: &[u8]| {
+            let mut chip8 = Chip8::new();
+            chip8.load_program(program).unwrap();
+            chip8.timing = Timing::Vip;
+            let mut clock = FrameClock::vip(&chip8);
+
+            let mut instructions = 1;
+            chip8.cycle().unwrap();
+            while !clock.tick(&chip8) {
+                chip8.cycle().unwrap();
+                instructions += 1;
+            }
+            instructions
+        };
+
+        // 6000: 46 cycles per instruction; 8014: 84 cycles per instruction.
+        let loads = frame(&[0x60, 0x00].repeat(100));
+        let adds = frame(&[0x80, 0x14].repeat(100));
+        assert_eq!(loads, 40);
+        assert_eq!(adds, 22);
+    }
+
+    #[test]
+    fn test_draw_waits_for_next_frame() {
+        let mut chip8 = Chip8::new();
+        chip8.load_program(&[0xD0, 0x15, 0x12, 0x02]).unwrap();
+        chip8.timing = Timing::Vip;
+        let mut clock = FrameClock::vip(&chip8);
+
+        // Held back, the draw costs nothing but the wait.
+        chip8.cycle().unwrap();
+        assert!(clock.tick(&chip8));
+        chip8.cycle().unwrap();
+        assert!(clock.tick(&chip8));
+        assert_eq!(chip8.machine_cycles(), 0);
+
+        chip8.vblank();
+        chip8.cycle().unwrap();
+        assert!(!clock.tick(&chip8));
+        assert_eq!(chip8.program_counter(), 0x202);
+        assert_eq!(chip8.machine_cycles(), vip_cycles(0xD015, 0, false) as u64);
+    }
+
+    #[test]
+    fn test_taken_skips_cost_more() {
+        // Skip if V0 = 0; jump over the next instruction
+        let mut chip8 = Chip8::new();
+        chip8
+            .load_program(&[0x30, 0x00, 0x00, 0x00, 0x12, 0x08])
+            .unwrap();
+        chip8.timing = Timing::Vip;
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.machine_cycles(), 54);
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.program_counter(), 0x208);
+        assert_eq!(chip8.machine_cycles(), 54 + 52);
+    }
+
+    #[test]
+    fn test_parse_timing() {
+        assert_eq!("VIP".parse(), Ok(Timing::Vip));
+        assert_eq!(Timing::Fixed.to_string(), "fixed");
+        assert!("fast".parse::<Timing>().is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use crate::{consts::MEMORY_SIZE, instruction::Instruction, variant::Variant};
+
+/// Most instructions in a block. Bounding it keeps invalidation cheap: a
+/// write can only land in blocks starting at most this many instructions
+/// before it.
+pub const MAX_BLOCK_LENGTH: usize = 32;
+/// Most instructions kept translated at once.
+pub const MAX_TRANSLATED: usize = 0x10000;
+
+/// A basic block: instructions decoded ahead of time that run one after the
+/// other. Only the last one can jump, skip, wait or write to memory, so the
+/// rest can run without looking at the program counter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub instructions: Vec<Instruction>,
+}
+
+impl Block {
+    /// Decodes the block starting at `start`. It is empty when not even one
+    /// instruction fits in memory there.
+    pub fn translate(memory: &[u8; MEMORY_SIZE], variant: Variant, start: u16) -> Self {
+        let mut instructions = Vec::new();
+        let mut address = start as usize;
+
+        while instructions.len() < MAX_BLOCK_LENGTH && address + 1 < MEMORY_SIZE {
+            let opcode = u16::from_be_bytes([memory[address], memory[address + 1]]);
+            let instruction = variant.decode(opcode);
+            instructions.push(instruction);
+            address += 2;
+
+            if ends_block(instruction) {
+                break;
+            }
+        }
+
+        Block { instructions }
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+}
+
+/// Whether the instruction after this one might not be the next one in
+/// memory, or might have just been overwritten.
+fn ends_block(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Return
+            | Instruction::MachineRoutine(_)
+            | Instruction::Jump(_)
+            | Instruction::Call(_)
+            | Instruction::JumpWithOffset(_)
+            | Instruction::SkipIfEqual(..)
+            | Instruction::SkipIfNotEqual(..)
+            | Instruction::SkipIfRegistersEqual(..)
+            | Instruction::SkipIfRegistersNotEqual(..)
+            | Instruction::SkipIfKey(_)
+            | Instruction::SkipIfNotKey(_)
+            | Instruction::Draw(..)
+            | Instruction::WaitForKey(_)
+            | Instruction::StoreBcd(_)
+            | Instruction::StoreRegisters(_)
+            | Instruction::Unknown(_)
+            | Instruction::SkipIfSecondKey(_)
+            | Instruction::SkipIfNotSecondKey(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chip8::Chip8, layout::Layout};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn memory(program: &[u8]) -> [u8; MEMORY_SIZE] {
+        let mut memory = [0; MEMORY_SIZE];
+        memory[0x200..0x200 + program.len()].copy_from_slice(program);
+        memory
+    }
+
+    #[test]
+    fn test_blocks_end_at_control_flow() {
+        // V0 = 1; V1 = 2; skip if V0 == 1; jump
+        let memory = memory(&[0x60, 0x01, 0x61, 0x02, 0x30, 0x01, 0x12, 0x00]);
+
+        let block = Block::translate(&memory, Variant::Chip8, 0x200);
+        assert_eq!(
+            block.instructions,
+            [
+                Instruction::Load(0, 1),
+                Instruction::Load(1, 2),
+                Instruction::SkipIfEqual(0, 1),
+            ]
+        );
+        assert_eq!(Block::translate(&memory, Variant::Chip8, 0x206).len(), 1);
+        assert!(Block::translate(&memory, Variant::Chip8, 0xFFF).is_empty());
+
+        let loads = [0x60, 0x00].repeat(MAX_BLOCK_LENGTH + 1);
+        assert_eq!(
+            Block::translate(&self::memory(&loads), Variant::Chip8, 0x200).len(),
+            MAX_BLOCK_LENGTH
+        );
+    }
+
+    /// A random instruction that can't fault on its own or jump out of a
+    /// program `length` instructions long, from the CHIP-8X additions too
+    /// with `chip8x`.
+    fn random_instruction(rng: &mut StdRng, length: u16, chip8x: bool) -> u16 {
+        let x = rng.random_range(0..0x10) << 8;
+        let y = rng.random_range(0..0x10) << 4;
+        let nn = rng.random_range(0..0x100);
+        let target = 0x200 + 2 * rng.random_range(0..length);
+
+        if chip8x && rng.random_bool(0.3) {
+            return match rng.random_range(0..6) {
+                0 => 0x02A0,
+                1 => 0x5001 | x | y,
+                2 => 0xB000 | x | y | rng.random_range(0..0x10),
+                3 => 0xE0F2 | x,
+                4 => 0xE0F5 | x,
+                _ => 0xF0F8 | x,
+            };
+        }
+
+        match rng.random_range(0..20) {
+            0 => 0x00E0,
+            1 => 0x1000 | target,
+            2 => 0x3000 | x | nn,
+            3 => 0x4000 | x | nn,
+            4 => 0x5000 | x | y,
+            5 => 0x6000 | x | nn,
+            6 => 0x7000 | x | nn,
+            7 => 0x8000 | x | y | [0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0xE][nn as usize % 9],
+            8 => 0x9000 | x | y,
+            9 => 0xA000 | rng.random_range(0x400..0xE00),
+            10 => 0xC000 | x | nn,
+            11 => 0xD000 | x | y | rng.random_range(0..0x10),
+            12 => 0xF007 | x,
+            13 => 0xF015 | x,
+            14 => 0xF018 | x,
+            15 => 0xF033 | x,
+            16 => 0xF055 | x,
+            17 => 0xE09E | x,
+            18 => 0xE0A1 | x,
+            _ => 0xF065 | x,
+        }
+    }
+
+    #[test]
+    fn test_writes_invalidate_blocks() {
+        // V2 = 1; I = 0x200; V0, V1 = 0x62, 0x05; store them over the first
+        // instruction, making it V2 = 5; jump back to it
+        let mut chip8 = Chip8::new();
+        chip8
+            .load_program(&[
+                0x62, 0x01, 0xA2, 0x00, 0x60, 0x62, 0x61, 0x05, 0xF1, 0x55, 0x12, 0x00,
+            ])
+            .unwrap();
+
+        chip8.run(6).unwrap();
+        assert_eq!(chip8.registers()[2], 1);
+
+        chip8.run(1).unwrap();
+        assert_eq!(chip8.registers()[2], 5);
+    }
+
+    fn assert_same_state(interpreted: &Chip8, translated: &Chip8) {
+        assert_eq!(interpreted.program_counter(), translated.program_counter());
+        assert_eq!(interpreted.index_register(), translated.index_register());
+        assert_eq!(interpreted.registers(), translated.registers());
+        assert_eq!(interpreted.stack(), translated.stack());
+        assert_eq!(interpreted.delay_timer, translated.delay_timer);
+        assert_eq!(interpreted.sound_timer, translated.sound_timer);
+        assert_eq!(interpreted.display, translated.display);
+        assert_eq!(interpreted.memory()[..], translated.memory()[..]);
+        assert_eq!(interpreted.colors(), translated.colors());
+        assert_eq!(interpreted.output_port, translated.output_port);
+    }
+
+    #[test]
+    fn test_matches_interpreter() {
+        let mut rng = StdRng::seed_from_u64(8);
+
+        for program in 0..200 {
+            let length = rng.random_range(1..64);
+            let variant = match program % 5 {
+                0 => Variant::Chip8x,
+                1 => Variant::Hires,
+                _ => Variant::Chip8,
+            };
+            // Two jumps at the end, so a skip can't run past it either.
+            let bytes: Vec<u8> = (0..length)
+                .map(|_| random_instruction(&mut rng, length, variant == Variant::Chip8x))
+                .chain([0x1200, 0x1200])
+                .flat_map(u16::to_be_bytes)
+                .collect();
+
+            let mut interpreted = Chip8::new();
+            let mut translated = Chip8::new();
+            for chip8 in [&mut interpreted, &mut translated] {
+                chip8.quirks.display_wait = program % 2 == 0;
+                if program % 3 == 0 {
+                    chip8.set_layout(Layout::Vip);
+                }
+                chip8.set_variant(variant);
+                chip8.seed(program);
+                chip8.load_program(&bytes).unwrap();
+            }
+
+            for frame in 0..30 {
+                // Stores can write over the program, so it may well fault, but
+                // on the same instruction either way.
+                let cycles = rng.random_range(0..50);
+                let result = (0..cycles).try_for_each(|_| interpreted.cycle());
+                assert_eq!(translated.run(cycles), result);
+                if result.is_err() {
+                    assert_same_state(&interpreted, &translated);
+                    break;
+                }
+
+                let key = rng.random_range(0..0x10);
+                if frame % 2 == 0 {
+                    interpreted.press_key(key);
+                    translated.press_key(key);
+                    interpreted.second_keypad[key as usize ^ 1] = true;
+                    translated.second_keypad[key as usize ^ 1] = true;
+                } else {
+                    interpreted.release_key(key);
+                    translated.release_key(key);
+                }
+                interpreted.vblank();
+                translated.vblank();
+
+                assert_same_state(&interpreted, &translated);
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    consts::{CHIP8X_PROGRAM_START_ADDRESS, HEIGHT, HIRES_HEIGHT, PROGRAM_START_ADDRESS},
+    instruction::Instruction,
+};
+
+/// Which instruction set programs are written for.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Variant {
+    #[default]
+    Chip8,
+    /// For the COSMAC VIP with the VP-590 colour board and the VP-595 sound
+    /// board: background and foreground colours, a second keypad and an I/O
+    /// port.
+    Chip8x,
+    /// The two-page hi-res CHIP-8 for the COSMAC VIP: 64x64 pixels, cleared
+    /// with `0230`. Picked by itself for programs starting with `1260`.
+    Hires,
+}
+
+impl Variant {
+    pub fn decode(self, opcode: u16) -> Instruction {
+        match self {
+            Variant::Chip8 => Instruction::decode(opcode),
+            Variant::Chip8x => Instruction::decode_chip8x(opcode),
+            Variant::Hires if opcode == 0x0230 => Instruction::Clear,
+            Variant::Hires => Instruction::decode(opcode),
+        }
+    }
+
+    pub fn display_height(self) -> usize {
+        match self {
+            Variant::Hires => HIRES_HEIGHT,
+            _ => HEIGHT,
+        }
+    }
+
+    /// Where the variant's interpreter loaded programs.
+    pub fn load_address(self) -> usize {
+        match self {
+            Variant::Chip8 | Variant::Hires => PROGRAM_START_ADDRESS,
+            Variant::Chip8x => CHIP8X_PROGRAM_START_ADDRESS,
+        }
+    }
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "chip8" => Ok(Variant::Chip8),
+            "chip8x" => Ok(Variant::Chip8x),
+            "hires" => Ok(Variant::Hires),
+            _ => Err(format!(
+                "unknown variant '{s}', expected chip8, chip8x or hires"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Variant::Chip8 => write!(f, "chip8"),
+            Variant::Chip8x => write!(f, "chip8x"),
+            Variant::Hires => write!(f, "hires"),
+        }
+    }
+}